@@ -9,12 +9,15 @@ use crossterm::{
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use embedded_io::{Read, Write};
 use embedded_io_adapters::std::FromStd;
 use log::info;
 use ratatui::prelude::*;
-use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
-use sts3215::{ServoError, info::render_tui, lerobot::robot::Robot};
+use serialport::SerialPort;
+use sts3215::{
+    ServoError,
+    info::render_tui,
+    lerobot::robot::{LimitPolicy, Robot},
+};
 
 pub fn main() -> Result<(), ServoError> {
     // Setup logging to file
@@ -44,8 +47,10 @@ pub fn main() -> Result<(), ServoError> {
 
     // let mut robot = Robot::<FromStd<Box<dyn SerialPort>>>::new_std_robot("/dev/cu.wchusbserial5AAF2185891")?;
     let mut robot = Robot::<FromStd<Box<dyn SerialPort>>>::new_std_robot("/dev/cu.wchusbserial5AAF2185891").unwrap();
+    // Holding an arrow key stops at the end of the range
+    robot.set_limit_policy(LimitPolicy::Clamp);
 
-    let result = run_app::<_, FromStd<Box<dyn SerialPort>>>(&mut terminal, &mut robot);
+    let result = run_app(&mut terminal, &mut robot);
 
     // Restore terminal
     disable_raw_mode()
@@ -64,7 +69,7 @@ pub fn main() -> Result<(), ServoError> {
     result
 }
 
-fn run_app<B: Backend, P: Read + Write>(
+fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     robot: &mut Robot<FromStd<Box<dyn SerialPort>>>,
 ) -> Result<(), ServoError> {
@@ -82,11 +87,13 @@ fn run_app<B: Backend, P: Read + Write>(
                 info!("Error processing queued commands: {:?}", e);
             }
         }
-        robot.update_servo_state()?;
+        if let Err(e) = robot.update_servo_state() {
+            info!("Error updating servo state: {:?}", e);
+        }
         // Update servo data
 
         terminal.draw(|f| {
-            render_tui::<_>(f, &robot, selected_servo_index);
+            render_tui(f, robot, selected_servo_index);
         }).map_err(|_| ServoError::IOError).unwrap();
 
         // Poll for events with a timeout
@@ -109,7 +116,7 @@ fn run_app<B: Backend, P: Read + Write>(
                         } else {
                             -20
                         };
-                        robot.send_relative_move_command(selected_servo_index as u8, delta, None, None).unwrap();
+                        robot.send_relative_move_command(selected_servo_index as u8, delta, None).unwrap();
                     }
                     KeyCode::Right => {
                         let delta = if key.modifiers.contains(KeyModifiers::SHIFT) {
@@ -117,7 +124,7 @@ fn run_app<B: Backend, P: Read + Write>(
                         } else {
                             20
                         };
-                        robot.send_relative_move_command(selected_servo_index as u8, delta, None, None).unwrap();
+                        robot.send_relative_move_command(selected_servo_index as u8, delta, None).unwrap();
                     }
                     _ => {}
                 }
@@ -139,16 +146,3 @@ pub fn select_previous(selected_index: &mut usize) {
         *selected_index -= 1;
     }
 }
-
-pub fn create_servo_port(port: &str) -> Result<Box<dyn SerialPort>, serialport::Error> {
-    let port = serialport::new(port, 1_000_000)
-        .timeout(Duration::from_millis(1000))
-        .data_bits(DataBits::Eight)
-        .stop_bits(StopBits::One)
-        .parity(Parity::None)
-        .flow_control(FlowControl::None)
-        .open()?;
-
-    info!("Port opened successfully");
-    Ok(port)
-}
@@ -2,27 +2,58 @@ use embedded_io::{Read, Write};
 
 use log::info;
 
-use crate::ServoError;
+use crate::{
+    MoveProfile, ServoError, ServoStatus, check_range,
+    frame::{read_error, read_frame},
+    registers::{
+        Acceleration, GoalPosition, Moving, PresentCurrent, PresentLoad, PresentPosition,
+        PresentSpeed, PresentTemperature, PresentVoltage, Register, Status,
+    },
+};
 
 const PING_ID: u8 = 0x01;
 const READ_DATA_ID: u8 = 0x02;
 const WRITE_DATA_ID: u8 = 0x03;
-
-pub const GOAL_POSITION_REGISTER: u8 = 0x2A;
-
-pub const POSITION_REGISTER: u8 = 0x38;
-pub const SPEED_REGISTER: u8 = 0x3a;
-pub const LOAD_REGISTER: u8 = 0x3c;
-pub const VOLTAGE_REGISTER: u8 = 0x3e;
-pub const TEMPERATURE_REGISTER: u8 = 0x3f; // ok
-pub const STATUS_REGISTER: u8 = 0x41;
-pub const MOVING_REGISTER: u8 = 0x42;
-pub const CURRENT_REGISTER: u8 = 0x43;
+const REG_WRITE_ID: u8 = 0x04;
+const ACTION_ID: u8 = 0x05;
+const RESET_ID: u8 = 0x06;
+const SYNC_READ_ID: u8 = 0x82;
+const SYNC_WRITE_ID: u8 = 0x83;
+
+/// Addresses every servo on the bus. Servos never reply to a broadcast packet.
+pub const BROADCAST_ID: u8 = 0xfe;
+
+pub const ACCELERATION_REGISTER: u8 = Acceleration::ADDRESS;
+pub const GOAL_POSITION_REGISTER: u8 = GoalPosition::ADDRESS;
+
+/// Length of the block a profiled move writes: acceleration, goal position, goal time and goal
+/// speed.
+pub const MOVE_BLOCK_LENGTH: usize = 7;
+
+pub const POSITION_REGISTER: u8 = PresentPosition::ADDRESS;
+pub const SPEED_REGISTER: u8 = PresentSpeed::ADDRESS;
+pub const LOAD_REGISTER: u8 = PresentLoad::ADDRESS;
+pub const VOLTAGE_REGISTER: u8 = PresentVoltage::ADDRESS;
+pub const TEMPERATURE_REGISTER: u8 = PresentTemperature::ADDRESS;
+pub const STATUS_REGISTER: u8 = Status::ADDRESS;
+pub const MOVING_REGISTER: u8 = Moving::ADDRESS;
+pub const CURRENT_REGISTER: u8 = PresentCurrent::ADDRESS;
+
+/// Length of the contiguous present-state block, from position up to and including current.
+pub const FEEDBACK_BLOCK_LENGTH: u8 = CURRENT_REGISTER + 2 - POSITION_REGISTER;
 
 pub(crate) enum Command<'a> {
     Ping(u8),
     Read(u8, u8, u8),
     Write(u8, u8, &'a [u8]),
+    /// Like `Write`, but the servo holds the data until an `Action` is broadcast.
+    RegWrite(u8, u8, &'a [u8]),
+    Action,
+    Reset(u8),
+    /// Start register, per-servo data length and the interleaved `[id, data..]` blocks.
+    SyncWrite(u8, u8, &'a [u8]),
+    /// Start register, number of bytes to read and the ids of the servos to read from.
+    SyncRead(u8, u8, &'a [u8]),
 }
 
 impl<'cmd> Command<'cmd> {
@@ -44,16 +75,50 @@ impl<'cmd> Command<'cmd> {
                 buffer[6] = *reply_length;
                 7
             }
-            Command::Write(servo_id, addr, data) => {
+            Command::Write(servo_id, addr, data) | Command::RegWrite(servo_id, addr, data) => {
                 buffer[2] = *servo_id;
                 buffer[3] = (3 + data.len()) as u8; // length = instruction + addr + data
-                buffer[4] = WRITE_DATA_ID;
+                buffer[4] = if matches!(self, Command::Write(..)) {
+                    WRITE_DATA_ID
+                } else {
+                    REG_WRITE_ID
+                };
                 buffer[5] = *addr;
                 for (i, &byte) in data.iter().enumerate() {
                     buffer[6 + i] = byte;
                 }
                 6 + data.len()
             }
+            Command::Reset(servo_id) => {
+                buffer[2] = *servo_id;
+                buffer[3] = 0x02;
+                buffer[4] = RESET_ID;
+                5
+            }
+            Command::Action => {
+                buffer[2] = BROADCAST_ID;
+                buffer[3] = 0x02;
+                buffer[4] = ACTION_ID;
+                5
+            }
+            Command::SyncWrite(addr, data_length, payload) => {
+                buffer[2] = BROADCAST_ID;
+                buffer[3] = (4 + payload.len()) as u8; // length = instruction + addr + data length + payload
+                buffer[4] = SYNC_WRITE_ID;
+                buffer[5] = *addr;
+                buffer[6] = *data_length;
+                buffer[7..7 + payload.len()].copy_from_slice(payload);
+                7 + payload.len()
+            }
+            Command::SyncRead(addr, reply_length, servo_ids) => {
+                buffer[2] = BROADCAST_ID;
+                buffer[3] = (4 + servo_ids.len()) as u8; // length = instruction + addr + reply length + ids
+                buffer[4] = SYNC_READ_ID;
+                buffer[5] = *addr;
+                buffer[6] = *reply_length;
+                buffer[7..7 + servo_ids.len()].copy_from_slice(servo_ids);
+                7 + servo_ids.len()
+            }
         };
         let chk = Self::calculate_checksum(buffer, checksum_index);
         buffer[checksum_index] = chk;
@@ -62,66 +127,182 @@ impl<'cmd> Command<'cmd> {
 
     fn calculate_checksum(buffer: &[u8], length: usize) -> u8 {
         let mut counter = 0_u8;
-        for index in 2..length {
-            let value = buffer[index];
-            counter = counter.wrapping_add(value);
+        for value in &buffer[2..length] {
+            counter = counter.wrapping_add(*value);
         }
 
         !counter
     }
 
-    pub(crate) fn send_command<'a, P: Write + Read>(
+    /// Total size of the encoded packet, including header and checksum.
+    fn packet_len(&self) -> usize {
+        match self {
+            Command::Ping(_) | Command::Reset(_) | Command::Action => 6,
+            Command::Read(..) => 8,
+            Command::Write(_, _, data) | Command::RegWrite(_, _, data) => 7 + data.len(),
+            Command::SyncWrite(_, _, payload) => 8 + payload.len(),
+            Command::SyncRead(_, _, servo_ids) => 8 + servo_ids.len(),
+        }
+    }
+
+    fn write_packet<P: Write + Read>(
         &self,
-        mut port: P,
-        buffer: &'a mut [u8],
-    ) -> Result<CommandResponse<'a>, ServoError> {
+        port: &mut P,
+        buffer: &mut [u8],
+    ) -> Result<usize, ServoError> {
+        // The length byte covers everything after it, so a packet can never exceed 255 + 4 bytes
+        if self.packet_len() > buffer.len() || self.packet_len() > 259 {
+            return Err(ServoError::CommandOverflow);
+        }
         let index = self.write_buffer(buffer);
         port.write_all(&buffer[..index])
             .map_err(|_| ServoError::WriteError)?;
         info!("Command buffer: {:02x?}", &buffer[..index]);
-        let read_count = port.read(buffer).map_err(|_| ServoError::ReadError)?;
-        info!("Response buffer: {:02x?}", &buffer[..read_count]);
-        CommandResponse::parse_response(&buffer[..read_count])
+        Ok(index)
+    }
+
+    pub(crate) fn send_command<'a, P: Write + Read>(
+        &self,
+        mut port: P,
+        buffer: &'a mut [u8],
+    ) -> Result<CommandResponse<'a>, ServoError> {
+        if !self.expects_response() {
+            self.send_without_response(&mut port, buffer)?;
+            return Ok(CommandResponse::no_reply());
+        }
+        self.write_packet(&mut port, buffer)?;
+        let response = CommandResponse::receive(port, buffer)?;
+        match self.servo_id() {
+            Some(servo_id) => response.expect_id(servo_id),
+            None => Ok(response),
+        }
+    }
+
+    /// Whether a single status packet answers the command. Broadcast packets get no reply, and a
+    /// SYNC READ gets one reply per servo.
+    fn expects_response(&self) -> bool {
+        match self.servo_id() {
+            Some(servo_id) => servo_id != BROADCAST_ID,
+            None => false,
+        }
+    }
+
+    /// The servo addressed by the command, or `None` for broadcast commands.
+    fn servo_id(&self) -> Option<u8> {
+        match self {
+            Command::Ping(servo_id)
+            | Command::Reset(servo_id)
+            | Command::Read(servo_id, _, _)
+            | Command::Write(servo_id, _, _)
+            | Command::RegWrite(servo_id, _, _) => Some(*servo_id),
+            Command::Action | Command::SyncWrite(..) | Command::SyncRead(..) => None,
+        }
+    }
+
+    /// Sends a command the servos do not answer, such as a SYNC WRITE.
+    pub(crate) fn send_without_response<P: Write + Read>(
+        &self,
+        mut port: P,
+        buffer: &mut [u8],
+    ) -> Result<(), ServoError> {
+        self.write_packet(&mut port, buffer)?;
+        // A half-duplex port reads back the echo on flush
+        port.flush().map_err(read_error)
+    }
+
+    /// Sends a command that is answered by one status packet per servo, such as a SYNC READ.
+    /// The replies are read with [`CommandResponse::receive`].
+    pub(crate) fn send_command_multi_response<P: Write + Read>(
+        &self,
+        mut port: P,
+        buffer: &mut [u8],
+    ) -> Result<(), ServoError> {
+        self.write_packet(&mut port, buffer)?;
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct CommandResponse<'a> {
-    _id: u8,
-    status: u8,
+    id: u8,
+    status: ServoStatus,
     data: &'a [u8],
 }
 
 impl<'a> CommandResponse<'a> {
+    /// Parses a status packet: `0xFF 0xFF id length status params.. checksum`, where length
+    /// counts the status, params and checksum bytes and the checksum covers id up to the params.
     fn parse_response(buffer: &'a [u8]) -> Result<CommandResponse<'a>, ServoError> {
-        // info!("Parsing response buffer: {:x?}", buffer);
+        if buffer.len() < 6 {
+            return Err(ServoError::ShortFrame);
+        }
         if buffer[0] != 0xFF || buffer[1] != 0xFF {
             info!("Invalid header");
-            return Err(ServoError::InvalidHeader(buffer[0], buffer[1])); // Invalid header
+            return Err(ServoError::InvalidHeader(buffer[0], buffer[1]));
         }
 
         let id = buffer[2];
-        let length = buffer[3] as usize;
-        let status = buffer[4];
-        let checksum = buffer[3 + length];
-
-        let calculated_checksum = Command::calculate_checksum(buffer, length);
-
-        if !calculated_checksum == checksum {
+        let length = buffer[3];
+        if length < 2 {
+            return Err(ServoError::LengthOutOfRange(length));
+        }
+        let checksum_index = 3 + length as usize;
+        let checksum = *buffer.get(checksum_index).ok_or(ServoError::ShortFrame)?;
+        let calculated_checksum = Command::calculate_checksum(buffer, checksum_index);
+        if calculated_checksum != checksum {
             info!("Checksum mismatch");
-            return Err(ServoError::ChecksumMismatch(calculated_checksum, checksum)); // Checksum mismatch
+            return Err(ServoError::ChecksumMismatch(calculated_checksum, checksum));
         }
 
-        let data = &buffer[5..5 + length - 2];
         Ok(Self {
-            _id: id,
-            status,
-            data,
+            id,
+            status: ServoStatus::from_bits_retain(buffer[4]),
+            data: &buffer[5..checksum_index],
         })
     }
 
+    /// Stands in for the status packet of a command the servos do not answer.
+    fn no_reply() -> Self {
+        Self {
+            id: BROADCAST_ID,
+            status: ServoStatus::empty(),
+            data: &[],
+        }
+    }
+
+    /// Checks that the response was sent by `servo_id`.
+    fn expect_id(self, servo_id: u8) -> Result<Self, ServoError> {
+        if self.id == servo_id {
+            Ok(self)
+        } else {
+            info!(
+                "Response from servo {} while expecting servo {}",
+                self.id, servo_id
+            );
+            Err(ServoError::IdMismatch(servo_id, self.id))
+        }
+    }
+
+    /// Reads exactly one status packet from the port, leaving any following packets unread.
+    pub(crate) fn receive<P: Read>(
+        mut port: P,
+        buffer: &'a mut [u8],
+    ) -> Result<CommandResponse<'a>, ServoError> {
+        let end = read_frame(&mut port, buffer)?;
+        info!("Response buffer: {:02x?}", &buffer[..end]);
+        CommandResponse::parse_response(&buffer[..end])
+    }
+
+    pub(crate) fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        self.data
+    }
+
     pub(crate) fn is_ok(&self) -> bool {
-        self.status == 0
+        self.status.is_empty()
     }
 
     pub(crate) fn is_error(&self) -> Result<(), ServoError> {
@@ -147,10 +328,6 @@ impl<'a> CommandResponse<'a> {
             None
         }
     }
-
-    pub(crate) fn status(&self) -> u8 {
-        self.status
-    }
 }
 
 pub fn send_ping<'a, P: Write + Read>(
@@ -166,26 +343,66 @@ pub fn write_position<'a, P: Write + Read>(
     buffer: &'a mut [u8],
     servo_id: u8,
     position: u16,
-    speed: Option<u16>,
-    acc: Option<u16>,
+    profile: Option<MoveProfile>,
 ) -> Result<CommandResponse<'a>, ServoError> {
-    let mut data = [0u8; 6];
-    let mut len = 0;
+    let block = move_block(position, profile)?;
+    let (address, data) = move_data(&block, profile);
+    info!("Writing buffer to servo {}: {:02x?}", servo_id, data);
+    Command::Write(servo_id, address, data).send_command(port, buffer)
+}
 
-    data[0..2].copy_from_slice(&position.to_le_bytes());
-    len += 2;
+pub fn send_reset<'a, P: Write + Read>(
+    port: &mut P,
+    buffer: &'a mut [u8],
+    servo_id: u8,
+) -> Result<CommandResponse<'a>, ServoError> {
+    Command::Reset(servo_id).send_command(port, buffer)
+}
 
-    if let Some(s) = speed {
-        data[len..len + 2].copy_from_slice(&s.to_le_bytes());
-        len += 2;
-    }
-    if let Some(a) = acc {
-        data[len..len + 2].copy_from_slice(&a.to_le_bytes());
-        len += 2;
-    }
+/// Stages a goal position on the servo, to be started by [`send_action`].
+pub fn reg_write_position<'a, P: Write + Read>(
+    port: &mut P,
+    buffer: &'a mut [u8],
+    servo_id: u8,
+    position: u16,
+    profile: Option<MoveProfile>,
+) -> Result<CommandResponse<'a>, ServoError> {
+    let block = move_block(position, profile)?;
+    let (address, data) = move_data(&block, profile);
+    info!("Staging buffer on servo {}: {:02x?}", servo_id, data);
+    Command::RegWrite(servo_id, address, data).send_command(port, buffer)
+}
 
-    info!("Writing buffer to servo {}: {:02x?}", servo_id, &data[..len]);
-    Command::Write(servo_id, GOAL_POSITION_REGISTER, &data[..len]).send_command(port, buffer)
+/// Broadcasts ACTION, starting the writes staged on every servo. Servos do not reply.
+pub fn send_action<P: Write + Read>(port: &mut P) -> Result<(), ServoError> {
+    let mut buffer = [0u8; 6];
+    Command::Action.send_without_response(port, &mut buffer)
+}
+
+/// The acceleration, goal position, goal time and goal speed registers of a move, in register
+/// order from [`ACCELERATION_REGISTER`]. Without a profile they are zero: full speed. Fails with
+/// [`ServoError::ValueOutOfRange`] for an acceleration above [`Acceleration::MAX`].
+pub(crate) fn move_block(
+    position: u16,
+    profile: Option<MoveProfile>,
+) -> Result<[u8; MOVE_BLOCK_LENGTH], ServoError> {
+    let (acceleration, time, speed) = profile.map_or((0, 0, 0), MoveProfile::registers);
+    check_range::<Acceleration>(acceleration)?;
+    let mut block = [0u8; MOVE_BLOCK_LENGTH];
+    block[0] = acceleration;
+    block[1..3].copy_from_slice(&position.to_le_bytes());
+    block[3..5].copy_from_slice(&time.to_le_bytes());
+    block[5..7].copy_from_slice(&speed.to_le_bytes());
+    Ok(block)
+}
+
+/// Start address and data a single move writes: without a profile only the goal position, so the
+/// servo keeps its current acceleration, time and speed.
+fn move_data(block: &[u8; MOVE_BLOCK_LENGTH], profile: Option<MoveProfile>) -> (u8, &[u8]) {
+    match profile {
+        Some(_) => (ACCELERATION_REGISTER, block),
+        None => (GOAL_POSITION_REGISTER, &block[1..3]),
+    }
 }
 
 #[cfg(test)]
@@ -252,4 +469,189 @@ mod tests {
         // Total length should be 13 (header + id + length + instruction + addr + 6 data bytes + checksum)
         assert_eq!(length, 13, "Total buffer length should be 13");
     }
+
+    #[test]
+    fn test_move_data() {
+        let block = move_block(2048, Some(MoveProfile::ByTime { millis: 800 })).unwrap();
+        assert_eq!(block, [0, 0x00, 0x08, 0x20, 0x03, 0, 0]);
+        assert_eq!(
+            move_data(&block, None),
+            (GOAL_POSITION_REGISTER, &[0x00, 0x08][..])
+        );
+
+        let profile = Some(MoveProfile::BySpeed {
+            speed: 1000,
+            acceleration: 50,
+        });
+        let block = move_block(4095, profile).unwrap();
+        assert_eq!(block, [50, 0xff, 0x0f, 0, 0, 0xe8, 0x03]);
+        assert_eq!(
+            move_data(&block, profile),
+            (ACCELERATION_REGISTER, &block[..])
+        );
+
+        let block =
+            move_block(100, Some(MoveProfile::ByAcceleration { acceleration: 10 })).unwrap();
+        assert_eq!(block, [10, 100, 0, 0, 0, 0, 0]);
+
+        assert!(matches!(
+            move_block(100, Some(MoveProfile::ByAcceleration { acceleration: 255 })),
+            Err(ServoError::ValueOutOfRange(0x29, 255))
+        ));
+    }
+
+    #[test]
+    fn test_sync_write_command_buffer() {
+        // Two servos, each receiving a 2 byte goal position
+        let payload = [0x01, 0x00, 0x08, 0x02, 0xff, 0x0f];
+        let cmd = Command::SyncWrite(GOAL_POSITION_REGISTER, 2, &payload);
+
+        let mut buffer = [0u8; 256];
+        let length = cmd.write_buffer(&mut buffer);
+
+        assert_eq!(
+            length, 14,
+            "Header, id, length, instruction, addr, data length, payload and checksum"
+        );
+        assert_eq!(length, cmd.packet_len());
+        assert_eq!(
+            &buffer[..7],
+            &[
+                0xFF,
+                0xFF,
+                BROADCAST_ID,
+                10,
+                SYNC_WRITE_ID,
+                GOAL_POSITION_REGISTER,
+                2
+            ]
+        );
+        assert_eq!(&buffer[7..13], &payload);
+
+        let expected_checksum = !buffer[2..13]
+            .iter()
+            .fold(0u8, |acc, value| acc.wrapping_add(*value));
+        assert_eq!(
+            buffer[13], expected_checksum,
+            "Checksum should be calculated correctly"
+        );
+    }
+
+    #[test]
+    fn test_sync_read_command_buffer() {
+        let servo_ids = [1, 2, 3];
+        let cmd = Command::SyncRead(POSITION_REGISTER, 2, &servo_ids);
+
+        let mut buffer = [0u8; 256];
+        let length = cmd.write_buffer(&mut buffer);
+
+        assert_eq!(length, 11);
+        assert_eq!(length, cmd.packet_len());
+        assert_eq!(
+            &buffer[..10],
+            &[
+                0xFF,
+                0xFF,
+                BROADCAST_ID,
+                7,
+                SYNC_READ_ID,
+                POSITION_REGISTER,
+                2,
+                1,
+                2,
+                3
+            ]
+        );
+
+        let expected_checksum = !buffer[2..10]
+            .iter()
+            .fold(0u8, |acc, value| acc.wrapping_add(*value));
+        assert_eq!(
+            buffer[10], expected_checksum,
+            "Checksum should be calculated correctly"
+        );
+    }
+
+    #[test]
+    fn test_reg_write_and_action_command_buffer() {
+        let mut buffer = [0u8; 256];
+        let length =
+            Command::RegWrite(2, GOAL_POSITION_REGISTER, &[0x00, 0x08]).write_buffer(&mut buffer);
+        assert_eq!(
+            &buffer[..length],
+            &[
+                0xFF,
+                0xFF,
+                2,
+                5,
+                REG_WRITE_ID,
+                GOAL_POSITION_REGISTER,
+                0x00,
+                0x08,
+                0xC2
+            ]
+        );
+
+        let length = Command::Action.write_buffer(&mut buffer);
+        assert_eq!(
+            &buffer[..length],
+            &[0xFF, 0xFF, BROADCAST_ID, 2, ACTION_ID, 0xFA]
+        );
+    }
+
+    #[test]
+    fn test_broadcast_does_not_wait_for_reply() {
+        // Nothing to read: waiting for a reply would time out
+        let mut port = crate::mock::MockPort::new(&[]);
+        let mut buffer = [0u8; 16];
+
+        let response = Command::Write(BROADCAST_ID, 0x28, &[0x00])
+            .send_command(&mut port, &mut buffer)
+            .unwrap();
+
+        assert!(response.is_ok());
+        assert_eq!(
+            &port.tx[..6],
+            &[0xFF, 0xFF, BROADCAST_ID, 4, WRITE_DATA_ID, 0x28]
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        // Read reply from servo 1 carrying position 2048
+        let packet = [0xFF, 0xFF, 0x01, 0x04, 0x00, 0x00, 0x08, 0xF2];
+        let response = CommandResponse::parse_response(&packet).unwrap();
+        assert_eq!(response.id(), 1);
+        assert!(response.is_ok());
+        assert_eq!(response.data_as_u16(), Some(2048));
+
+        assert!(
+            response
+                .expect_id(2)
+                .is_err_and(|e| matches!(e, ServoError::IdMismatch(2, 1)))
+        );
+
+        let mut corrupted = packet;
+        corrupted[6] = 0x09;
+        assert!(matches!(
+            CommandResponse::parse_response(&corrupted),
+            Err(ServoError::ChecksumMismatch(0xF1, 0xF2))
+        ));
+        assert!(matches!(
+            CommandResponse::parse_response(&packet[..7]),
+            Err(ServoError::ShortFrame)
+        ));
+        assert!(matches!(
+            CommandResponse::parse_response(&packet[..3]),
+            Err(ServoError::ShortFrame)
+        ));
+        assert!(matches!(
+            CommandResponse::parse_response(&[0xFF, 0xFF, 0x01, 0x01, 0x00, 0xFD]),
+            Err(ServoError::LengthOutOfRange(1))
+        ));
+        assert!(matches!(
+            CommandResponse::parse_response(&[0xFF, 0xFE, 0x01, 0x02, 0x00, 0xFC]),
+            Err(ServoError::InvalidHeader(0xFF, 0xFE))
+        ));
+    }
 }
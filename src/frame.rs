@@ -0,0 +1,149 @@
+use embedded_io::{Error, ErrorKind, Read};
+
+use crate::ServoError;
+
+const HEADER: u8 = 0xff;
+
+/// Smallest valid length byte: instruction/status plus checksum.
+const MIN_LENGTH: u8 = 2;
+
+/// Reads bytes from the port until a complete status packet sits at the start of `buffer`, and
+/// returns its length.
+///
+/// Bytes before the `0xFF 0xFF` header are discarded, so line noise or a partial packet left over
+/// from an earlier timeout does not prevent the next packet from being found. The decoder never
+/// reads past the end of the packet, leaving any following packets on the port.
+pub(crate) fn read_frame<P: Read>(mut port: P, buffer: &mut [u8]) -> Result<usize, ServoError> {
+    let mut filled = 0;
+    loop {
+        let needed = if filled < 4 {
+            4
+        } else {
+            let length = buffer[3];
+            let needed = 4 + length as usize;
+            if length < MIN_LENGTH || needed > buffer.len() {
+                return Err(ServoError::LengthOutOfRange(length));
+            }
+            needed
+        };
+        if filled == needed {
+            return Ok(needed);
+        }
+        if needed > buffer.len() {
+            return Err(ServoError::CommandOverflow);
+        }
+
+        match port.read(&mut buffer[filled..needed]) {
+            Ok(0) => return Err(incomplete(filled)),
+            Ok(count) => filled += count,
+            Err(e) if e.kind() == ErrorKind::TimedOut => return Err(incomplete(filled)),
+            Err(e) => return Err(read_error(e)),
+        }
+        filled = resync(buffer, filled);
+    }
+}
+
+/// Maps a port error to a [`ServoError`]. [`crate::half_duplex::HalfDuplex`] reports a bad echo
+/// as [`ErrorKind::InvalidData`].
+pub(crate) fn read_error<E: Error>(e: E) -> ServoError {
+    if e.kind() == ErrorKind::InvalidData {
+        ServoError::EchoMismatch
+    } else {
+        ServoError::ReadError
+    }
+}
+
+fn incomplete(filled: usize) -> ServoError {
+    if filled == 0 {
+        ServoError::Timeout
+    } else {
+        ServoError::ShortFrame
+    }
+}
+
+/// Moves the first plausible packet start to the front of the buffer and returns the number of
+/// bytes kept. A header is `0xFF 0xFF` followed by an id other than `0xFF`.
+fn resync(buffer: &mut [u8], filled: usize) -> usize {
+    let start = (0..filled)
+        .find(|&index| {
+            let bytes = &buffer[index..filled];
+            bytes[0] == HEADER
+                && bytes.get(1).is_none_or(|&byte| byte == HEADER)
+                && bytes.get(2).is_none_or(|&byte| byte != HEADER)
+        })
+        .unwrap_or(filled);
+    buffer.copy_within(start..filled, 0);
+    filled - start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockPort, status_packet};
+
+    #[test]
+    fn test_read_frame_reassembles_chunks() {
+        let packet = status_packet(1, &[0x00, 0x08]);
+        let mut port = MockPort::new(&packet).with_chunk_size(3);
+        let mut buffer = [0u8; 32];
+
+        let length = read_frame(&mut port, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..length], packet.as_slice());
+    }
+
+    #[test]
+    fn test_read_frame_resyncs_on_header() {
+        let packet = status_packet(2, &[0x2a]);
+        let mut rx = std::vec![0x00, 0x13, 0xff, 0xff, 0xff];
+        rx.extend_from_slice(&packet[2..]);
+        let mut port = MockPort::new(&rx).with_chunk_size(2);
+        let mut buffer = [0u8; 32];
+
+        let length = read_frame(&mut port, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..length], packet.as_slice());
+    }
+
+    #[test]
+    fn test_read_frame_leaves_following_packets() {
+        let mut rx = status_packet(1, &[]);
+        rx.extend(status_packet(2, &[]));
+        let mut port = MockPort::new(&rx);
+        let mut buffer = [0u8; 32];
+
+        read_frame(&mut port, &mut buffer).unwrap();
+        let length = read_frame(&mut port, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..length], status_packet(2, &[]).as_slice());
+    }
+
+    #[test]
+    fn test_read_frame_errors() {
+        let mut buffer = [0u8; 32];
+
+        let mut port = MockPort::new(&[]);
+        assert!(matches!(
+            read_frame(&mut port, &mut buffer),
+            Err(ServoError::Timeout)
+        ));
+
+        let mut port = MockPort::new(&[0xff, 0xff, 0x01, 0x04, 0x00]);
+        assert!(matches!(
+            read_frame(&mut port, &mut buffer),
+            Err(ServoError::ShortFrame)
+        ));
+
+        let mut port = MockPort::new(&[0xff, 0xff, 0x01, 0x01, 0x00]);
+        assert!(matches!(
+            read_frame(&mut port, &mut buffer),
+            Err(ServoError::LengthOutOfRange(1))
+        ));
+
+        let mut port = MockPort::new(&[0xff, 0xff, 0x01, 0x40, 0x00]);
+        assert!(matches!(
+            read_frame(&mut port, &mut buffer),
+            Err(ServoError::LengthOutOfRange(0x40))
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+//! Support for one-wire buses where TX and RX are tied together.
+//!
+//! On such a bus every byte written to the servos is also received back by the UART, so each
+//! status packet is preceded by an echo of the command. [`HalfDuplex`] wraps a port and
+//! consumes and verifies that echo, so the rest of the crate only ever sees the servos' replies.
+
+use embedded_io::{Error, ErrorKind, ErrorType, Read, ReadExactError, Write};
+
+/// Longest packet the crate writes: the length byte covers at most 255 bytes after the header.
+const MAX_ECHO_LENGTH: usize = 259;
+
+/// Port wrapper for half-duplex buses that echo transmitted bytes.
+///
+/// Bytes written through the wrapper are remembered and read back before the next read or on
+/// `flush`. A mismatch is reported as an error of kind [`ErrorKind::InvalidData`], which the
+/// crate's functions surface as [`crate::ServoError::EchoMismatch`].
+pub struct HalfDuplex<P> {
+    port: P,
+    pending_echo: heapless::Vec<u8, MAX_ECHO_LENGTH>,
+}
+
+#[derive(Debug)]
+pub enum HalfDuplexError<E> {
+    /// Error from the wrapped port.
+    Io(E),
+    /// The bytes read back differ from the bytes written, or stopped short.
+    EchoMismatch,
+}
+
+impl<E: Error> Error for HalfDuplexError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            HalfDuplexError::Io(e) => e.kind(),
+            HalfDuplexError::EchoMismatch => ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl<P> HalfDuplex<P> {
+    pub fn new(port: P) -> Self {
+        Self {
+            port,
+            pending_echo: heapless::Vec::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.port
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+}
+
+impl<P: Read> HalfDuplex<P> {
+    /// Reads back the echo of everything written since the last check.
+    fn consume_echo(&mut self) -> Result<(), HalfDuplexError<P::Error>> {
+        if self.pending_echo.is_empty() {
+            return Ok(());
+        }
+        let mut echo = [0u8; MAX_ECHO_LENGTH];
+        let echo = &mut echo[..self.pending_echo.len()];
+        let result = self.port.read_exact(echo);
+        let matches = echo == self.pending_echo.as_slice();
+        self.pending_echo.clear();
+        match result {
+            Ok(()) if matches => Ok(()),
+            Ok(()) | Err(ReadExactError::UnexpectedEof) => Err(HalfDuplexError::EchoMismatch),
+            Err(ReadExactError::Other(e)) => Err(HalfDuplexError::Io(e)),
+        }
+    }
+}
+
+impl<P: ErrorType> ErrorType for HalfDuplex<P> {
+    type Error = HalfDuplexError<P::Error>;
+}
+
+impl<P: Read> Read for HalfDuplex<P> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.consume_echo()?;
+        self.port.read(buf).map_err(HalfDuplexError::Io)
+    }
+}
+
+impl<P: Read + Write> Write for HalfDuplex<P> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.pending_echo.is_full() {
+            self.consume_echo()?;
+        }
+        let room = self.pending_echo.capacity() - self.pending_echo.len();
+        let written = self
+            .port
+            .write(&buf[..buf.len().min(room)])
+            .map_err(HalfDuplexError::Io)?;
+        // Cannot fail, the write was limited to the free space
+        let _ = self.pending_echo.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    /// Flushes the port and consumes the echo, so commands without a reply leave no bytes behind.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.port.flush().map_err(HalfDuplexError::Io)?;
+        self.consume_echo()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ServoError, action,
+        comm::send_ping,
+        mock::{MockPort, status_packet},
+    };
+
+    #[test]
+    fn test_echo_is_skipped_before_reply() {
+        let ping = [0xff, 0xff, 0x01, 0x02, 0x01, 0xfb];
+        let mut rx = ping.to_vec();
+        rx.extend(status_packet(1, &[]));
+        let mut port = HalfDuplex::new(MockPort::new(&rx).with_chunk_size(4));
+        let mut buffer = [0u8; 32];
+
+        let response = send_ping(&mut port, &mut buffer, 1).unwrap();
+
+        assert!(response.is_ok());
+        assert!(port.inner().rx.is_empty());
+    }
+
+    #[test]
+    fn test_echo_of_broadcast_is_consumed_on_flush() {
+        let action_packet = [0xff, 0xff, 0xfe, 0x02, 0x05, 0xfa];
+        let mut port = HalfDuplex::new(MockPort::new(&action_packet));
+
+        action(&mut port).unwrap();
+
+        assert!(port.inner().rx.is_empty());
+    }
+
+    #[test]
+    fn test_corrupted_echo_is_reported() {
+        let mut rx = std::vec![0xff, 0xff, 0x01, 0x02, 0x01, 0x00];
+        rx.extend(status_packet(1, &[]));
+        let mut port = HalfDuplex::new(MockPort::new(&rx));
+        let mut buffer = [0u8; 32];
+
+        assert!(matches!(
+            send_ping(&mut port, &mut buffer, 1),
+            Err(ServoError::EchoMismatch)
+        ));
+    }
+}
@@ -3,7 +3,11 @@ use ratatui::{prelude::*, widgets::*};
 
 use crate::lerobot::robot::Robot;
 
-pub fn render_tui<PORT: Read + Write>(f: &mut Frame, robot: &Robot<PORT>, selected_index: usize) {
+pub fn render_tui<PORT: Read + Write, const N: usize, const BUFFER_SIZE: usize>(
+    f: &mut Frame,
+    robot: &Robot<PORT, N, BUFFER_SIZE>,
+    selected_index: usize,
+) {
     let area = f.area();
 
     // Create the table header
@@ -13,7 +17,7 @@ pub fn render_tui<PORT: Read + Write>(f: &mut Frame, robot: &Robot<PORT>, select
         Cell::from("Goal Position"),
         Cell::from("Speed"),
         Cell::from("Temp (°C)"),
-        Cell::from("Load"),
+        Cell::from("Load (‰)"),
         Cell::from("Voltage"),
         Cell::from("Current"),
         Cell::from("Moving"),
@@ -43,10 +47,10 @@ pub fn render_tui<PORT: Read + Write>(f: &mut Frame, robot: &Robot<PORT>, select
                 } else {
                     Style::default().fg(Color::Gray)
                 }),
-                Cell::from(if info.has_error { "Yes" } else { "No" }).style(if info.has_error {
-                    Style::default().fg(Color::Red)
-                } else {
+                Cell::from(info.status.to_string()).style(if info.status.is_empty() {
                     Style::default().fg(Color::Green)
+                } else {
+                    Style::default().fg(Color::Red)
                 }),
             ]);
 
@@ -72,7 +76,7 @@ pub fn render_tui<PORT: Read + Write>(f: &mut Frame, robot: &Robot<PORT>, select
             Constraint::Length(10), // Voltage
             Constraint::Length(10), // Voltage
             Constraint::Length(8),  // Moving
-            Constraint::Length(8),  // Error
+            Constraint::Length(24), // Error
         ],
     )
     .header(header)
@@ -1,115 +1,264 @@
 use log::info;
 
 use crate::{
-    ServoError,
+    Mode, MoveProfile, ServoError, ServoStatus, action, broadcast_disable_torque,
     comm::{
-        CURRENT_REGISTER, LOAD_REGISTER, MOVING_REGISTER, POSITION_REGISTER, SPEED_REGISTER,
-        STATUS_REGISTER, TEMPERATURE_REGISTER, VOLTAGE_REGISTER, send_ping, write_position,
+        CURRENT_REGISTER, Command, FEEDBACK_BLOCK_LENGTH, LOAD_REGISTER, MOVING_REGISTER,
+        POSITION_REGISTER, SPEED_REGISTER, STATUS_REGISTER, TEMPERATURE_REGISTER, VOLTAGE_REGISTER,
+        send_ping, write_position,
     },
-    has_error, is_moving, read_current, read_load, read_position, read_speed, read_temperature,
-    read_u8_register, read_u16_register, read_voltage,
+    move_steps, read_angle, read_angle_limits, read_angular_velocity, read_current_draw, read_load,
+    read_position_offset, read_speed, read_status, read_supply_voltage, read_u8_register,
+    read_u16_register, reg_write_position,
+    registers::{
+        Id, LOAD_SIGN_BIT, MinAngleLimit, OperatingMode, Register, SPEED_SIGN_BIT, TorqueLimit,
+        decode_sign_magnitude,
+    },
+    reset_servo, scan_bus, set_middle_position, set_operating_mode, set_servo_id, sync_read,
+    sync_write_positions,
+    trajectory::{Trajectory, period},
+    tuning::ServoTuning,
+    units::{Angle, AngularVelocity, Current, MAX_POSITION, Voltage, round},
+    write_angle_limits, write_goal_speed, write_position_offset, write_pwm, write_torque_limit,
 };
+use core::{ops::RangeInclusive, time::Duration};
+
 use embedded_io::{Read, Write};
 
-#[derive(Default, Debug, Clone, Copy)]
-pub struct ServoPositionCommand {
-    pub id: u8,
-    pub position: u16,
-    pub speed: Option<u16>,
-    pub acc: Option<u16>,
-}
+pub use crate::ServoPositionCommand;
+
 #[derive(Default, Debug, Clone, Copy)]
 pub struct ServoInfo {
     pub id: u8,
     pub position: u16,
     pub goal_position: u16,
-    pub speed: u16,
+    /// In steps/s, negative when turning in reverse.
+    pub speed: i16,
     pub temperature: u8,
-    pub load: u16,
+    /// In ‰ of the maximum torque, negative when loaded in reverse.
+    pub load: i16,
     pub voltage: u8,
     pub current: u16,
     pub is_moving: bool,
-    pub has_error: bool,
+    pub status: ServoStatus,
 }
 
+impl ServoInfo {
+    /// Decodes the present-state block that starts at [`POSITION_REGISTER`].
+    fn from_feedback(servo_id: u8, block: &[u8]) -> Result<Self, ServoError> {
+        let block = block
+            .get(..FEEDBACK_BLOCK_LENGTH as usize)
+            .ok_or(ServoError::ReadError)?;
+        let u8_at = |register: u8| block[(register - POSITION_REGISTER) as usize];
+        let u16_at = |register: u8| u16::from_le_bytes([u8_at(register), u8_at(register + 1)]);
+
+        let position = u16_at(POSITION_REGISTER);
+        Ok(ServoInfo {
+            id: servo_id,
+            position,
+            goal_position: position,
+            speed: decode_sign_magnitude(u16_at(SPEED_REGISTER), SPEED_SIGN_BIT),
+            temperature: u8_at(TEMPERATURE_REGISTER),
+            load: decode_sign_magnitude(u16_at(LOAD_REGISTER), LOAD_SIGN_BIT),
+            voltage: u8_at(VOLTAGE_REGISTER),
+            current: u16_at(CURRENT_REGISTER),
+            is_moving: u8_at(MOVING_REGISTER) != 0,
+            status: ServoStatus::from_bits_retain(u8_at(STATUS_REGISTER)),
+        })
+    }
+}
+
+/// Confirms the factory reset of one servo, see [`Robot::factory_reset`].
+#[derive(Debug)]
+pub struct ResetConfirmation {
+    servo_id: u8,
+}
+
+impl ResetConfirmation {
+    /// Confirms that resetting `servo_id` may erase its ID, limits, offsets and tuning.
+    pub fn erase_configuration_of(servo_id: u8) -> Self {
+        Self { servo_id }
+    }
+}
+
+/// What [`ServoState`] does with a goal position outside the soft limits of its joint.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// Refuses the goal with [`ServoError::LimitExceeded`].
+    #[default]
+    Reject,
+    /// Moves to the nearest limit instead.
+    Clamp,
+}
+
+/// Number of distinct servo ids on a bus, so a scan can count every servo that answers.
+const MAX_SERVOS: usize = Id::MAX as usize + 1;
+
 #[derive(Debug)]
 pub struct ServoState<const SERVO_COUNT: usize, const COMMAND_QUEUE_SIZE: usize = 16> {
     pub infos: [ServoInfo; SERVO_COUNT],
     pub servo_ids: [u8; SERVO_COUNT],
+    /// Operating mode of each servo as last set or read by the [`Robot`], `None` until known.
+    pub modes: [Option<Mode>; SERVO_COUNT],
+    /// Goal positions each servo may be sent, enforced before anything is written to the bus.
+    pub soft_limits: [RangeInclusive<u16>; SERVO_COUNT],
+    pub limit_policy: LimitPolicy,
     pub queued_commands: heapless::Vec<ServoPositionCommand,COMMAND_QUEUE_SIZE>,
 }
 
 impl<const N: usize> ServoState<N> {
     pub fn new(servo_ids: &[u8; N]) -> Self {
         Self {
-            servo_ids: servo_ids.clone(),
+            servo_ids: *servo_ids,
             infos: [ServoInfo::default(); N],
+            modes: [None; N],
+            soft_limits: core::array::from_fn(|_| 0..=MAX_POSITION),
+            limit_policy: LimitPolicy::Reject,
             queued_commands: heapless::Vec::new(),
         }
     }
 
 
-    pub fn update<P: Read + Write>(&mut self, port: &mut P, buffer: &mut [u8]) {
-        for (index, &id) in self.servo_ids.iter().enumerate() {
-            if let Ok(info) = read_servo_info(port, buffer, id) {
-                self.infos[index] = info;
+    /// Polls the present state of all servos with one SYNC READ.
+    ///
+    /// Servos that answer get their info updated even when others fail; a servo that fails keeps
+    /// its previous info and the first failure is returned.
+    pub fn update<P: Read + Write>(
+        &mut self,
+        port: &mut P,
+        buffer: &mut [u8],
+    ) -> Result<(), ServoError> {
+        let replies = sync_read(
+            port,
+            buffer,
+            POSITION_REGISTER,
+            FEEDBACK_BLOCK_LENGTH,
+            &self.servo_ids,
+        )?;
+        let mut result = Ok(());
+        for (index, reply) in replies.into_iter().enumerate() {
+            let id = self.servo_ids[index];
+            match reply.and_then(|block| ServoInfo::from_feedback(id, &block)) {
+                Ok(info) => self.infos[index] = info,
+                Err(e) => {
+                    info!("Error reading servo {}: {:?}", id, e);
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Fails with [`ServoError::WrongMode`] when `servo_id` is one of these servos and not in
+    /// position mode, where goal positions would be taken as speeds, PWM or relative steps.
+    pub fn check_position_mode(&self, servo_id: u8) -> Result<(), ServoError> {
+        self.check_mode(servo_id, Mode::Position)
+    }
+
+    /// Fails with [`ServoError::WrongMode`] when `servo_id` is one of these servos and not in
+    /// `mode`, or with [`ServoError::UnknownMode`] when its mode has not been read yet.
+    pub fn check_mode(&self, servo_id: u8, mode: Mode) -> Result<(), ServoError> {
+        let Some(index) = self.servo_ids.iter().position(|id| *id == servo_id) else {
+            return Ok(());
+        };
+        match self.modes[index] {
+            Some(known) if known == mode => Ok(()),
+            Some(known) => Err(ServoError::WrongMode(servo_id, known)),
+            None => Err(ServoError::UnknownMode(servo_id)),
+        }
+    }
+
+    /// Checks a goal position for `servo_id` against its mode and soft limits, and returns the
+    /// position to send. Servos that are not part of this state may use the full range.
+    pub fn check_goal(&self, servo_id: u8, goal: i32) -> Result<u16, ServoError> {
+        self.check_position_mode(servo_id)?;
+        let (min, max) = match self.servo_ids.iter().position(|id| *id == servo_id) {
+            Some(index) => {
+                let limits = &self.soft_limits[index];
+                (*limits.start() as i32, *limits.end() as i32)
             }
+            None => (0, MAX_POSITION as i32),
+        };
+        if (min..=max).contains(&goal) {
+            return Ok(goal as u16);
+        }
+        match self.limit_policy {
+            LimitPolicy::Reject => Err(ServoError::LimitExceeded(servo_id, goal)),
+            LimitPolicy::Clamp => Ok(goal.clamp(min, max) as u16),
         }
     }
 
-    pub fn send_absolute_move_command(&mut self, servo_index: u8, position: u16, speed: Option<u16>, acc: Option<u16>)->Result<(), ServoError> {
+    pub fn send_absolute_move_command(
+        &mut self,
+        servo_index: u8,
+        position: u16,
+        profile: Option<MoveProfile>,
+    ) -> Result<(), ServoError> {
         let servo_id = self.servo_ids[servo_index as usize];
+        let position = self.check_goal(servo_id, position as i32)?;
         self.infos[servo_index as usize].goal_position = position;
-        self.queued_commands.push(ServoPositionCommand {
+        self.queue_command(ServoPositionCommand {
             id: servo_id,
             position,
-            speed,
-            acc,
-        }).map_err(|_| ServoError::CommandOverflow)        
+            profile,
+        })
     }
-    pub fn send_relative_move_command(&mut self, servo_index: u8, delta: i16, speed: Option<u16>, acc: Option<u16>)->Result<(), ServoError> {
+    pub fn send_relative_move_command(
+        &mut self,
+        servo_index: u8,
+        delta: i16,
+        profile: Option<MoveProfile>,
+    ) -> Result<(), ServoError> {
         let servo_id = self.servo_ids[servo_index as usize];
-        let new_position = self.infos[servo_index as usize].goal_position as i16 + delta;
-        self.infos[servo_index as usize].goal_position = new_position.rem_euclid(4096) as u16;
+        let new_position = self.infos[servo_index as usize].goal_position as i32 + delta as i32;
+        self.infos[servo_index as usize].goal_position = self.check_goal(servo_id, new_position)?;
         info!(
             "Queued position command for servo {}: new_position={}",
             servo_id, self.infos[servo_index as usize].goal_position
         );
-        self.queued_commands.push(ServoPositionCommand {
+        self.queue_command(ServoPositionCommand {
             id: servo_id,
             position: self.infos[servo_index as usize].goal_position,
-            speed: speed,
-            acc: acc,
-        }).map_err(|_| ServoError::CommandOverflow)
+            profile,
+        })
     }
 
+    /// Queues a command, replacing a pending command for the same servo: only the latest goal
+    /// matters, and a SYNC WRITE must not address a servo twice.
+    fn queue_command(&mut self, command: ServoPositionCommand) -> Result<(), ServoError> {
+        if let Some(queued) = self
+            .queued_commands
+            .iter_mut()
+            .find(|queued| queued.id == command.id)
+        {
+            *queued = command;
+            return Ok(());
+        }
+        self.queued_commands
+            .push(command)
+            .map_err(|_| ServoError::CommandOverflow)
+    }
+
+    /// Sends all queued commands to the servos with [`sync_write_positions`].
     pub fn process_queued_commands<P: Read + Write>(
         &mut self,
         port: &mut P,
         buffer: &mut [u8],
     ) -> Result<(), ServoError> {
-        if let Some(command) = self.queued_commands.pop() {
-            let response = write_position(
-                port,
-                buffer,
-                command.id,
-                command.position,
-                command.speed,
-                command.acc,
-            )?;
-            info!(
-                "Sent position command to servo {}: position={}, speed={:?}, acc={:?}",
-                command.id, command.position, command.speed, command.acc
-            );
-            if response.is_ok() {
-                Ok(())
-            } else {
-                Err(ServoError::StatusError(response.status()))
-            }
-        } else {
+        if self.queued_commands.is_empty() {
             info!("No queued commands to process.");
-            Ok(())
+            return Ok(());
         }
+        sync_write_positions(port, buffer, &self.queued_commands)?;
+        info!(
+            "Sent {} queued position commands: {:?}",
+            self.queued_commands.len(),
+            self.queued_commands
+        );
+        self.queued_commands.clear();
+        Ok(())
     }
 
     // pub fn read_servo_set<const N: usize, P: Read + Write>(
@@ -125,61 +274,200 @@ impl<const N: usize> ServoState<N> {
     // }
 }
 
-fn read_servo_info<P: Read + Write>(
+/// Reads the whole present-state block of a servo with a single READ.
+pub fn read_servo_info<P: Read + Write>(
     port: &mut P,
     buffer: &mut [u8],
     servo_id: u8,
 ) -> Result<ServoInfo, ServoError> {
-    let position = read_position(port, buffer, servo_id).unwrap_or(0);
-    let speed = read_speed(port, buffer, servo_id).unwrap_or(0);
-    let temperature = read_temperature(port, buffer, servo_id).unwrap_or(0);
-    let load = read_load(port, buffer, servo_id).unwrap_or(0);
-    let voltage = read_voltage(port, buffer, servo_id).unwrap_or(0);
-    let current = read_current(port, buffer, servo_id).unwrap_or(0);
-    let is_moving = is_moving(port, buffer, servo_id).unwrap_or(false);
-    let has_error = has_error(port, buffer, servo_id).unwrap_or(true);
-    Ok(ServoInfo {
-        id: servo_id,
-        position,
-        goal_position: position,
-        speed,
-        temperature,
-        load,
-        voltage,
-        current,
-        is_moving,
-        has_error,
-    })
+    let response = Command::Read(servo_id, POSITION_REGISTER, FEEDBACK_BLOCK_LENGTH)
+        .send_command(port, buffer)?;
+    ServoInfo::from_feedback(servo_id, response.data())
+}
+
+/// Buffer size of a [`Robot`] unless chosen with [`RobotBuilder::buffer_size`].
+pub const DEFAULT_BUFFER_SIZE: usize = 256;
+
+/// A set of servos on one serial port.
+///
+/// `N` is the number of servos and `BUFFER_SIZE` the size of the packet buffer, which must hold
+/// the largest packet sent or received. [`Robot::new`] builds the six servo arm with ids 1-6,
+/// [`Robot::builder`] any other configuration.
+pub struct Robot<
+    PORT: Read + Write,
+    const N: usize = 6,
+    const BUFFER_SIZE: usize = DEFAULT_BUFFER_SIZE,
+> {
+    port: PORT,
+    servo_state: ServoState<N>,
+    joint_names: Option<[&'static str; N]>,
+    buffer: [u8; BUFFER_SIZE],
 }
 
-pub struct Robot<PORT: Read + Write> {
+/// Configures a [`Robot`], see [`Robot::builder`].
+pub struct RobotBuilder<PORT: Read + Write, const N: usize, const BUFFER_SIZE: usize> {
     port: PORT,
-    servo_state: ServoState<6>,
-    buffer: [u8; 256],
+    servo_ids: [u8; N],
+    joint_names: Option<[&'static str; N]>,
+}
+
+impl<PORT: Read + Write, const N: usize, const BUFFER_SIZE: usize>
+    RobotBuilder<PORT, N, BUFFER_SIZE>
+{
+    /// Sets the ids of the servos; joint indices follow the order of `servo_ids`. Clears joint
+    /// names set before.
+    pub fn servo_ids<const M: usize>(
+        self,
+        servo_ids: [u8; M],
+    ) -> RobotBuilder<PORT, M, BUFFER_SIZE> {
+        RobotBuilder {
+            port: self.port,
+            servo_ids,
+            joint_names: None,
+        }
+    }
+
+    /// Names the joints, in the same order as the servo ids.
+    pub fn joint_names(mut self, joint_names: [&'static str; N]) -> Self {
+        self.joint_names = Some(joint_names);
+        self
+    }
+
+    pub fn buffer_size<const SIZE: usize>(self) -> RobotBuilder<PORT, N, SIZE> {
+        RobotBuilder {
+            port: self.port,
+            servo_ids: self.servo_ids,
+            joint_names: self.joint_names,
+        }
+    }
+
+    /// Builds the robot and reads the operating mode of every servo, so position moves only go
+    /// to servos in position mode. A servo that does not answer is logged and keeps an unknown
+    /// mode until [`Robot::refresh_operating_modes`]. Fails with [`ServoError::IdInUse`] when an
+    /// id is listed twice.
+    pub fn build(mut self) -> Result<Robot<PORT, N, BUFFER_SIZE>, ServoError> {
+        for (index, id) in self.servo_ids.iter().enumerate() {
+            if self.servo_ids[..index].contains(id) {
+                return Err(ServoError::IdInUse(*id));
+            }
+        }
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let mut servo_state = ServoState::new(&self.servo_ids);
+        // Silent servos are already logged and refuse commands until their mode is known
+        let _ = read_operating_modes(&mut self.port, &mut buffer, &mut servo_state);
+        Ok(Robot {
+            port: self.port,
+            servo_state,
+            joint_names: self.joint_names,
+            buffer,
+        })
+    }
 }
 
 impl <PORT: Read + Write>Robot<PORT> {
+    /// A six servo arm, such as the SO-100, with servo ids 1-6.
     pub fn  new(port: PORT) -> Result<Self, ServoError> {
-        let servo_ids = [1u8, 2, 3, 4, 5, 6];
-        let state = ServoState::new(&servo_ids);
-        let buffer = [0u8; 256];
+        Robot::builder(port).servo_ids([1u8, 2, 3, 4, 5, 6]).build()
+    }
+
+    /// Starts configuring a robot with other servo ids, joint names or buffer size:
+    ///
+    /// ```ignore
+    /// let pan_tilt = Robot::builder(port)
+    ///     .servo_ids([1, 2, 3, 4])
+    ///     .joint_names(["pan", "tilt", "roll", "zoom"])
+    ///     .build()?;
+    /// ```
+    pub fn builder(port: PORT) -> RobotBuilder<PORT, 0, DEFAULT_BUFFER_SIZE> {
+        RobotBuilder {
+            port,
+            servo_ids: [],
+            joint_names: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn new_std_robot(port_name: &str) -> Result<Robot<super::std::StdPort>, ServoError> {
+        super::std::new_std_robot(port_name, crate::BaudRate::B1000000)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn new_std_robot_with_baud(
+        port_name: &str,
+        baud_rate: crate::BaudRate,
+    ) -> Result<Robot<super::std::StdPort>, ServoError> {
+        super::std::new_std_robot(port_name, baud_rate)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn new_std_half_duplex_robot(
+        port_name: &str,
+    ) -> Result<Robot<crate::half_duplex::HalfDuplex<super::std::StdPort>>, ServoError> {
+        super::std::new_std_half_duplex_robot(port_name)
+    }
+}
+
+impl<PORT: Read + Write, const N: usize, const BUFFER_SIZE: usize> Robot<PORT, N, BUFFER_SIZE> {
+    /// Scans `id_range` for servos and builds a robot from the servos that answer, in id order,
+    /// reading their operating modes like [`RobotBuilder::build`]. Fails with
+    /// [`ServoError::ServoCountMismatch`] unless exactly `N` servos answer.
+    pub fn discover(mut port: PORT, id_range: RangeInclusive<u8>) -> Result<Self, ServoError> {
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let found = scan_bus::<_, MAX_SERVOS>(&mut port, &mut buffer, id_range)?;
+        let servo_ids: [u8; N] =
+            core::array::from_fn(|index| found.get(index).map_or(0, |servo| servo.id));
+        if found.len() != N {
+            return Err(ServoError::ServoCountMismatch(N, found.len()));
+        }
+        let mut servo_state = ServoState::new(&servo_ids);
+        let _ = read_operating_modes(&mut port, &mut buffer, &mut servo_state);
         Ok(Robot {
             port,
+            servo_state,
+            joint_names: None,
             buffer,
-            servo_state: state,
         })
     }
 
+    /// Opens a robot on a serial port with the servos found in `id_range`, see [`Robot::discover`].
     #[cfg(feature = "std")]
-    pub fn new_std_robot(port_name: &str) ->Result<Robot<embedded_io_adapters::std::FromStd<Box<dyn serialport::SerialPort>>>, ServoError> {
-        super::std::new_std_robot(port_name)
+    pub fn discover_std_robot(
+        port_name: &str,
+        id_range: RangeInclusive<u8>,
+    ) -> Result<Robot<super::std::StdPort, N, BUFFER_SIZE>, ServoError> {
+        super::std::discover_std_robot(port_name, id_range)
     }
 
-    pub fn send_absolute_move_command(&mut self, servo_index: u8, position: u16, time: Option<u16>, accel: Option<u16>)->Result<(), ServoError> {
-        self.servo_state.send_absolute_move_command(servo_index, position, time, accel)
+    /// The name of the joint at `joint_index`, when joint names were configured.
+    pub fn joint_name(&self, joint_index: usize) -> Option<&'static str> {
+        self.joint_names
+            .and_then(|names| names.get(joint_index).copied())
     }
-    pub fn send_relative_move_command(&mut self, servo_id: u8, delta: i16, time: Option<u16>, accel: Option<u16>)->Result<(), ServoError> {
-        self.servo_state.send_relative_move_command(servo_id, delta, time, accel)
+
+    /// The index of the joint called `name`.
+    pub fn joint_index(&self, name: &str) -> Option<usize> {
+        self.joint_names?
+            .iter()
+            .position(|joint_name| *joint_name == name)
+    }
+
+    pub fn send_absolute_move_command(
+        &mut self,
+        servo_index: u8,
+        position: u16,
+        profile: Option<MoveProfile>,
+    ) -> Result<(), ServoError> {
+        self.servo_state
+            .send_absolute_move_command(servo_index, position, profile)
+    }
+    pub fn send_relative_move_command(
+        &mut self,
+        servo_index: u8,
+        delta: i16,
+        profile: Option<MoveProfile>,
+    ) -> Result<(), ServoError> {
+        self.servo_state
+            .send_relative_move_command(servo_index, delta, profile)
     }
 
     pub fn process_queued_commands(
@@ -188,12 +476,93 @@ impl <PORT: Read + Write>Robot<PORT> {
         self.servo_state.process_queued_commands(&mut self.port, &mut self.buffer)
     }
 
-    pub fn update_servo_state(&mut self)->Result<(),ServoError> {
-        self.servo_state.update(&mut self.port, &mut self.buffer);
+    /// Moves every joint to its goal in `goals` so that all of them arrive together after
+    /// `duration`, by giving each joint a speed in proportion to the distance it travels. All
+    /// goals go out in one SYNC WRITE; nothing is sent when any goal is refused.
+    ///
+    /// Distances are taken from the positions in [`ServoState`], so call
+    /// [`Robot::update_servo_state`] first.
+    pub fn move_to_pose(&mut self, goals: &[u16; N], duration: Duration) -> Result<(), ServoError> {
+        let starts = self.servo_state.infos.map(|info| info.position);
+        self.move_between_poses(&starts, goals, duration)?;
+        Ok(())
+    }
+
+    /// Streams the setpoints of `trajectory` at `rate_hz`, each as a coordinated move like
+    /// [`Robot::move_to_pose`] over the time since the previous setpoint. Between setpoints
+    /// `delay` is called with the time until the next one, e.g. `std::thread::sleep`, or a timer
+    /// on an embedded target; it is not called after the last.
+    ///
+    /// The first setpoint is reached within one period, so move to the first waypoint with
+    /// [`Robot::move_to_pose`] beforehand.
+    pub fn execute_trajectory(
+        &mut self,
+        trajectory: &Trajectory<'_, N>,
+        rate_hz: u32,
+        mut delay: impl FnMut(Duration),
+    ) -> Result<(), ServoError> {
+        let mut previous = self.servo_state.infos.map(|info| info.position);
+        let mut previous_time = None;
+        let mut setpoints = trajectory.setpoints(rate_hz).peekable();
+        while let Some((time, setpoint)) = setpoints.next() {
+            let step = previous_time.map_or(period(rate_hz), |previous_time| time - previous_time);
+            previous = self.move_between_poses(&previous, &setpoint, step)?;
+            previous_time = Some(time);
+            if let Some((next_time, _)) = setpoints.peek() {
+                delay(*next_time - time);
+            }
+        }
         Ok(())
     }
+
+    /// Sends `goals` in one SYNC WRITE with speeds that take every joint from `starts` to its goal
+    /// in `duration`. Returns the goals as sent, after the soft limits.
+    fn move_between_poses(
+        &mut self,
+        starts: &[u16; N],
+        goals: &[u16; N],
+        duration: Duration,
+    ) -> Result<[u16; N], ServoError> {
+        let millis = duration.as_millis().max(1);
+        let mut commands: heapless::Vec<ServoPositionCommand, N> = heapless::Vec::new();
+        for (index, goal) in goals.iter().enumerate() {
+            let servo_id = self.servo_state.servo_ids[index];
+            let position = self.servo_state.check_goal(servo_id, *goal as i32)?;
+            let distance = position.abs_diff(starts[index]) as u128;
+            // A speed of 0 means full speed, so a joint that stays put still gets 1
+            let speed = distance
+                .saturating_mul(1000)
+                .div_ceil(millis)
+                .clamp(1, u16::MAX as u128);
+            let profile = MoveProfile::BySpeed {
+                speed: speed as u16,
+                acceleration: 0,
+            };
+            commands
+                .push(ServoPositionCommand {
+                    id: servo_id,
+                    position,
+                    profile: Some(profile),
+                })
+                .map_err(|_| ServoError::CommandOverflow)?;
+        }
+        sync_write_positions(&mut self.port, &mut self.buffer, &commands)?;
+        for (info, command) in self.servo_state.infos.iter_mut().zip(&commands) {
+            info.goal_position = command.position;
+        }
+        Ok(core::array::from_fn(|index| commands[index].position))
+    }
+
+    pub fn update_servo_state(&mut self) -> Result<(), ServoError> {
+        self.servo_state.update(&mut self.port, &mut self.buffer)
+    }
     
-    pub fn servo_state(&self) -> &ServoState<6> {
+    #[cfg(feature = "std")]
+    pub(crate) fn port_mut(&mut self) -> &mut PORT {
+        &mut self.port
+    }
+
+    pub fn servo_state(&self) -> &ServoState<N> {
         &self.servo_state
     }
 
@@ -201,22 +570,262 @@ impl <PORT: Read + Write>Robot<PORT> {
         &mut self,
         servo_id: u8,
         position: u16,
-        speed: Option<u16>,
-        acc: Option<u16>,
+        profile: Option<MoveProfile>,
     ) -> Result<(), ServoError> {
-        // let prt = &mut *self.port;
-
+        let position = self.servo_state.check_goal(servo_id, position as i32)?;
         write_position(
             &mut self.port,
             &mut self.buffer,
             servo_id,
             position,
-            speed,
-            acc,
+            profile,
         )?
         .is_error()
     }
 
+    pub fn move_to_angle(
+        &mut self,
+        servo_id: u8,
+        angle: Angle,
+        profile: Option<MoveProfile>,
+    ) -> Result<(), ServoError> {
+        self.move_to_position(servo_id, angle.to_steps(), profile)
+    }
+
+    /// Stages a move on a servo. The servo holds it until [`Robot::trigger_staged_moves`], so
+    /// several joints can be staged and then start moving together.
+    pub fn stage_move(
+        &mut self,
+        servo_id: u8,
+        position: u16,
+        profile: Option<MoveProfile>,
+    ) -> Result<(), ServoError> {
+        let position = self.servo_state.check_goal(servo_id, position as i32)?;
+        reg_write_position(
+            &mut self.port,
+            &mut self.buffer,
+            servo_id,
+            position,
+            profile,
+        )
+    }
+
+    /// Starts all staged moves at the same instant.
+    pub fn trigger_staged_moves(&mut self) -> Result<(), ServoError> {
+        action(&mut self.port)
+    }
+
+    /// Restores the factory configuration of a servo, see [`crate::reset_servo`]. The
+    /// confirmation must name the same servo.
+    ///
+    /// Returns the ID the servo answers on afterwards, normally [`crate::FACTORY_DEFAULT_ID`]. A
+    /// servo managed by this robot keeps its place under that id, back in [`Mode::Position`].
+    pub fn factory_reset(
+        &mut self,
+        servo_id: u8,
+        confirmation: ResetConfirmation,
+        delay: impl FnMut(Duration),
+    ) -> Result<u8, ServoError> {
+        if confirmation.servo_id != servo_id {
+            return Err(ServoError::ResetNotConfirmed(servo_id));
+        }
+        let new_id = reset_servo(&mut self.port, &mut self.buffer, servo_id, delay)?;
+        if let Some(index) = self.replace_servo_id(servo_id, new_id) {
+            self.servo_state.modes[index] = Some(Mode::Position);
+        }
+        Ok(new_id)
+    }
+
+    /// Changes the id of a servo, see [`crate::set_servo_id`]. A servo managed by this robot
+    /// keeps its place under the new id.
+    pub fn set_servo_id(&mut self, old_id: u8, new_id: u8) -> Result<(), ServoError> {
+        set_servo_id(&mut self.port, &mut self.buffer, old_id, new_id)?;
+        self.replace_servo_id(old_id, new_id);
+        Ok(())
+    }
+
+    /// The id of the servo at `joint_index`, or [`ServoError::JointNotFound`] past the last joint.
+    fn joint_servo_id(&self, joint_index: usize) -> Result<u8, ServoError> {
+        self.servo_state
+            .servo_ids
+            .get(joint_index)
+            .copied()
+            .ok_or(ServoError::JointNotFound(joint_index))
+    }
+
+    /// Moves the servo of this robot with `old_id` to `new_id` and returns its index.
+    fn replace_servo_id(&mut self, old_id: u8, new_id: u8) -> Option<usize> {
+        let index = self
+            .servo_state
+            .servo_ids
+            .iter()
+            .position(|id| *id == old_id)?;
+        self.servo_state.servo_ids[index] = new_id;
+        self.servo_state.infos[index].id = new_id;
+        Some(index)
+    }
+
+    /// Restricts the goal positions of a joint. Goals outside are rejected or clamped according
+    /// to [`Robot::set_limit_policy`]. Fails with [`ServoError::ValueOutOfRange`] when the range
+    /// is reversed.
+    pub fn set_soft_limits(
+        &mut self,
+        joint_index: usize,
+        limits: RangeInclusive<u16>,
+    ) -> Result<(), ServoError> {
+        self.joint_servo_id(joint_index)?;
+        check_soft_limits(&limits)?;
+        self.servo_state.soft_limits[joint_index] = limits;
+        Ok(())
+    }
+
+    pub fn set_limit_policy(&mut self, policy: LimitPolicy) {
+        self.servo_state.limit_policy = policy;
+    }
+
+    /// Reads the angle limits stored in a joint, see [`crate::read_angle_limits`].
+    pub fn read_angle_limits(
+        &mut self,
+        joint_index: usize,
+    ) -> Result<RangeInclusive<u16>, ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        read_angle_limits(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    /// Stores the angle limits of a joint, see [`crate::write_angle_limits`], and uses them as
+    /// its soft limits too.
+    pub fn write_angle_limits(
+        &mut self,
+        joint_index: usize,
+        limits: RangeInclusive<u16>,
+    ) -> Result<(), ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        write_angle_limits(&mut self.port, &mut self.buffer, servo_id, limits.clone())?;
+        self.servo_state.soft_limits[joint_index] = limits;
+        Ok(())
+    }
+
+    /// Uses the angle limits stored in every joint as its soft limits. Fails with
+    /// [`ServoError::ValueOutOfRange`] when a servo stores a reversed range.
+    pub fn load_soft_limits(&mut self) -> Result<(), ServoError> {
+        for index in 0..N {
+            let servo_id = self.servo_state.servo_ids[index];
+            let limits = read_angle_limits(&mut self.port, &mut self.buffer, servo_id)?;
+            check_soft_limits(&limits)?;
+            self.servo_state.soft_limits[index] = limits;
+        }
+        Ok(())
+    }
+
+    /// Stores the position correction of a joint, see [`crate::write_position_offset`].
+    pub fn set_joint_offset(&mut self, joint_index: usize, offset: i16) -> Result<(), ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        write_position_offset(&mut self.port, &mut self.buffer, servo_id, offset)
+    }
+
+    pub fn read_joint_offset(&mut self, joint_index: usize) -> Result<i16, ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        read_position_offset(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    /// Makes the current position of a joint its mid-point, see [`crate::set_middle_position`].
+    /// Place the joint at its zero by hand first.
+    pub fn calibrate_joint_middle(&mut self, joint_index: usize) -> Result<(), ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        set_middle_position(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    /// Stores the tuning of a joint and reads it back to check that the servo kept it.
+    pub fn tune_joint(
+        &mut self,
+        joint_index: usize,
+        tuning: &ServoTuning,
+    ) -> Result<(), ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        tuning.write(&mut self.port, &mut self.buffer, servo_id)?;
+        tuning.verify(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    pub fn read_joint_tuning(&mut self, joint_index: usize) -> Result<ServoTuning, ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        ServoTuning::read(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    /// Switches a servo to another operating mode, see [`crate::set_operating_mode`]. Position
+    /// moves to a servo of this robot are refused until it is back in [`Mode::Position`].
+    pub fn set_operating_mode(&mut self, servo_id: u8, mode: Mode) -> Result<(), ServoError> {
+        set_operating_mode(&mut self.port, &mut self.buffer, servo_id, mode)?;
+        self.remember_mode(servo_id, mode);
+        Ok(())
+    }
+
+    /// Reads the operating mode of every servo again, for servos that did not answer before or
+    /// were reconfigured behind this robot's back. Servos that answer get their mode updated even
+    /// when others fail; the first failure is returned.
+    pub fn refresh_operating_modes(&mut self) -> Result<(), ServoError> {
+        read_operating_modes(&mut self.port, &mut self.buffer, &mut self.servo_state)
+    }
+
+    /// The operating mode of a servo of this robot as last set or read, `None` while unknown.
+    pub fn operating_mode(&self, servo_id: u8) -> Option<Mode> {
+        let index = self
+            .servo_state
+            .servo_ids
+            .iter()
+            .position(|id| *id == servo_id)?;
+        self.servo_state.modes[index]
+    }
+
+    fn remember_mode(&mut self, servo_id: u8, mode: Mode) {
+        if let Some(index) = self
+            .servo_state
+            .servo_ids
+            .iter()
+            .position(|id| *id == servo_id)
+        {
+            self.servo_state.modes[index] = Some(mode);
+        }
+    }
+
+    /// Sets the PWM output of a servo in PWM mode, see [`crate::write_pwm`]. Fails with
+    /// [`ServoError::WrongMode`] for a servo of this robot in another mode.
+    pub fn write_pwm(&mut self, servo_id: u8, pwm: i16) -> Result<(), ServoError> {
+        self.servo_state.check_mode(servo_id, Mode::Pwm)?;
+        write_pwm(&mut self.port, &mut self.buffer, servo_id, pwm)
+    }
+
+    /// Moves a servo in step mode by a relative number of steps. Fails with
+    /// [`ServoError::WrongMode`] for a servo of this robot in another mode.
+    pub fn move_steps(&mut self, servo_id: u8, steps: i16) -> Result<(), ServoError> {
+        self.servo_state.check_mode(servo_id, Mode::Step)?;
+        move_steps(&mut self.port, &mut self.buffer, servo_id, steps)
+    }
+
+    /// Limits the torque of a joint, in 0.1% of the maximum torque, see
+    /// [`crate::write_torque_limit`].
+    pub fn set_torque_limit(&mut self, joint_index: usize, limit: u16) -> Result<(), ServoError> {
+        let servo_id = self.joint_servo_id(joint_index)?;
+        write_torque_limit(&mut self.port, &mut self.buffer, servo_id, limit)
+    }
+
+    /// Makes a joint compliant, so it can be moved by hand. 0.0 is fully stiff and 1.0 fully
+    /// limp; values in between hold against gravity while giving way to a push. Values outside
+    /// that range are clamped, and NaN or infinity fails with [`ServoError::InvalidCompliance`]
+    /// rather than letting the joint drop. Torque must be enabled for the joint to hold at all.
+    pub fn set_compliance(&mut self, joint_index: usize, fraction: f32) -> Result<(), ServoError> {
+        if !fraction.is_finite() {
+            return Err(ServoError::InvalidCompliance);
+        }
+        let stiffness = 1.0 - fraction.clamp(0.0, 1.0);
+        let limit = round(stiffness * TorqueLimit::MAX as f32) as u16;
+        self.set_torque_limit(joint_index, limit)
+    }
+
+    /// Disables torque on every servo on the bus with one broadcast packet.
+    pub fn emergency_stop(&mut self) -> Result<(), ServoError> {
+        broadcast_disable_torque(&mut self.port)
+    }
+
     pub fn ping_servo(&mut self, servo_id: u8) -> Result<(), ServoError> {
         send_ping(&mut self.port, &mut self.buffer, servo_id)?.is_error()
     }
@@ -248,6 +857,10 @@ impl <PORT: Read + Write>Robot<PORT> {
             .map(|value| value != 0)
     }
 
+    pub fn read_status(&mut self, servo_id: u8) -> Result<ServoStatus, ServoError> {
+        read_status(&mut self.port, &mut self.buffer, servo_id)
+    }
+
     pub fn read_position<P: Write + Read>(
         port: &mut P,
         buffer: &mut [u8],
@@ -256,13 +869,512 @@ impl <PORT: Read + Write>Robot<PORT> {
         read_u16_register(port, buffer, servo_id, POSITION_REGISTER)
     }
 
-    pub fn read_speed(&mut self, servo_id: u8) -> Result<u16, ServoError> {
-        read_u16_register(&mut self.port, &mut self.buffer, servo_id, SPEED_REGISTER)
+    pub fn read_speed(&mut self, servo_id: u8) -> Result<i16, ServoError> {
+        read_speed(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    pub fn read_load(&mut self, servo_id: u8) -> Result<i16, ServoError> {
+        read_load(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    /// Sets the speed of a servo in wheel mode, in steps/s; the sign selects the direction.
+    /// Fails with [`ServoError::WrongMode`] for a negative speed to a servo of this robot that is
+    /// not in wheel mode, where the sign bit would be taken as part of the speed.
+    pub fn write_goal_speed(&mut self, servo_id: u8, speed: i16) -> Result<(), ServoError> {
+        if speed < 0 {
+            self.servo_state.check_mode(servo_id, Mode::Wheel)?;
+        }
+        write_goal_speed(&mut self.port, &mut self.buffer, servo_id, speed)
+    }
+
+    pub fn read_angle(&mut self, servo_id: u8) -> Result<Angle, ServoError> {
+        read_angle(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    pub fn read_angular_velocity(&mut self, servo_id: u8) -> Result<AngularVelocity, ServoError> {
+        read_angular_velocity(&mut self.port, &mut self.buffer, servo_id)
     }
 
-    pub fn read_load(&mut self, servo_id: u8) -> Result<u16, ServoError> {
-        read_u16_register(&mut self.port, &mut self.buffer, servo_id, LOAD_REGISTER)
+    pub fn read_supply_voltage(&mut self, servo_id: u8) -> Result<Voltage, ServoError> {
+        read_supply_voltage(&mut self.port, &mut self.buffer, servo_id)
+    }
+
+    pub fn read_current_draw(&mut self, servo_id: u8) -> Result<Current, ServoError> {
+        read_current_draw(&mut self.port, &mut self.buffer, servo_id)
     }
 
     // Robot related methods would go here
 }
+
+#[cfg(feature = "std")]
+impl<const N: usize, const BUFFER_SIZE: usize> Robot<super::std::StdPort, N, BUFFER_SIZE> {
+    /// Switches a servo to another baud rate, and the port along with it. Servos still at the old
+    /// rate stop answering until they are switched as well.
+    pub fn set_servo_baud(
+        &mut self,
+        servo_id: u8,
+        baud_rate: crate::BaudRate,
+    ) -> Result<(), ServoError> {
+        crate::set_servo_baud(
+            &mut self.port,
+            &mut self.buffer,
+            servo_id,
+            baud_rate,
+            super::std::set_port_baud,
+        )
+    }
+
+    /// Finds the baud rate of a servo, leaving the port at that rate.
+    pub fn detect_baud_rate(&mut self, servo_id: u8) -> Result<crate::BaudRate, ServoError> {
+        crate::detect_baud_rate(
+            &mut self.port,
+            &mut self.buffer,
+            servo_id,
+            super::std::set_port_baud,
+        )
+    }
+}
+
+/// Reads the operating mode of every servo in `state` with one SYNC READ. Servos that fail are
+/// logged and keep the mode they had; the first failure is returned.
+fn read_operating_modes<P: Read + Write, const N: usize>(
+    port: &mut P,
+    buffer: &mut [u8],
+    state: &mut ServoState<N>,
+) -> Result<(), ServoError> {
+    let replies = match sync_read(port, buffer, OperatingMode::ADDRESS, 1, &state.servo_ids) {
+        Ok(replies) => replies,
+        Err(e) => {
+            info!("Error reading operating modes: {:?}", e);
+            return Err(e);
+        }
+    };
+    let mut result = Ok(());
+    for (index, reply) in replies.into_iter().enumerate() {
+        let mode =
+            reply.and_then(|block| Mode::from_code(block[0]).ok_or(ServoError::ResponseParseError));
+        match mode {
+            Ok(mode) => state.modes[index] = Some(mode),
+            Err(e) => {
+                info!(
+                    "Error reading the mode of servo {}: {:?}",
+                    state.servo_ids[index], e
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Soft limits may be a single position, but not a reversed range that clamping would panic on.
+fn check_soft_limits(limits: &RangeInclusive<u16>) -> Result<(), ServoError> {
+    if limits.start() > limits.end() {
+        return Err(ServoError::ValueOutOfRange(
+            MinAngleLimit::ADDRESS,
+            *limits.start(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockPort, status_packet};
+
+    /// Replies of servos in position mode to the mode read when a robot is built.
+    fn position_mode_replies(servo_ids: &[u8]) -> std::vec::Vec<u8> {
+        servo_ids
+            .iter()
+            .flat_map(|id| status_packet(*id, &[Mode::Position.code()]))
+            .collect()
+    }
+
+    /// Builds a robot with servos in position mode that then answers with `replies`, and forgets
+    /// the mode read that was sent.
+    fn build_robot<const N: usize>(servo_ids: [u8; N], replies: &[u8]) -> Robot<MockPort, N> {
+        let port = MockPort::new(&position_mode_replies(&servo_ids)).reply(replies);
+        let mut robot = Robot::builder(port).servo_ids(servo_ids).build().unwrap();
+        robot.port.tx.clear();
+        robot
+    }
+
+    #[test]
+    fn test_servo_info_from_feedback() {
+        let block = [
+            0x00, 0x08, // position 2048
+            0x32, 0x80, // speed -50
+            0x64, 0x00, // load 100
+            0x78, // voltage 12.0V
+            0x23, // temperature 35°C
+            0x00, // async write flag
+            0x00, // status
+            0x01, // moving
+            0x00, 0x00, // reserved
+            0x0a, 0x00, // current 10
+        ];
+        let info = ServoInfo::from_feedback(3, &block).unwrap();
+
+        assert_eq!(info.id, 3);
+        assert_eq!(info.position, 2048);
+        assert_eq!(info.goal_position, 2048);
+        assert_eq!(info.speed, -50);
+        assert_eq!(info.load, 100);
+        assert_eq!(info.voltage, 120);
+        assert_eq!(info.temperature, 35);
+        assert!(info.is_moving);
+        assert!(info.status.is_empty());
+        assert_eq!(info.current, 10);
+
+        assert!(
+            ServoInfo::from_feedback(3, &block[..10]).is_err(),
+            "A short block is an error"
+        );
+    }
+
+    #[test]
+    fn test_update_reports_missing_servo() {
+        let mut block = [0u8; FEEDBACK_BLOCK_LENGTH as usize];
+        block[..2].copy_from_slice(&1234u16.to_le_bytes());
+        // Servo 1 answers, servo 2 is silent
+        let mut robot = build_robot([1, 2], &status_packet(1, &block));
+        robot.servo_state.infos[1].position = 99;
+
+        assert!(matches!(
+            robot.update_servo_state(),
+            Err(ServoError::Timeout)
+        ));
+        assert_eq!(robot.servo_state().infos[0].position, 1234);
+        assert_eq!(
+            robot.servo_state().infos[1].position,
+            99,
+            "Servo 2 keeps its previous info"
+        );
+    }
+
+    #[test]
+    fn test_robot_builder() {
+        let robot = Robot::builder(MockPort::new(&position_mode_replies(&[11, 12, 13, 14])))
+            .servo_ids([11, 12, 13, 14])
+            .joint_names(["pan", "tilt", "roll", "zoom"])
+            .buffer_size::<64>()
+            .build()
+            .unwrap();
+
+        assert_eq!(robot.servo_state().servo_ids, [11, 12, 13, 14]);
+        assert_eq!(robot.joint_name(1), Some("tilt"));
+        assert_eq!(robot.joint_index("zoom"), Some(3));
+        assert_eq!(robot.joint_index("elbow"), None);
+
+        // The mode read goes out as one SYNC READ of the mode register
+        assert_eq!(&robot.port.tx[4..7], &[0x82, 0x21, 1]);
+
+        let duplicate = Robot::builder(MockPort::new(&[]))
+            .servo_ids([1, 2, 1])
+            .build();
+        assert!(matches!(duplicate, Err(ServoError::IdInUse(1))));
+    }
+
+    #[test]
+    fn test_silent_servo_has_unknown_mode() {
+        // Servo 2 is unplugged when the robot is built and answers the later refresh
+        let port = MockPort::new(&status_packet(1, &[Mode::Position.code()]))
+            .silence()
+            .reply(&status_packet(1, &[Mode::Position.code()]))
+            .reply(&status_packet(2, &[Mode::Wheel.code()]));
+        let mut robot = Robot::builder(port).servo_ids([1, 2]).build().unwrap();
+
+        assert_eq!(robot.operating_mode(2), None);
+        assert!(matches!(
+            robot.send_absolute_move_command(1, 1000, None),
+            Err(ServoError::UnknownMode(2))
+        ));
+        robot.send_absolute_move_command(0, 1000, None).unwrap();
+
+        robot.refresh_operating_modes().unwrap();
+        assert_eq!(robot.operating_mode(2), Some(Mode::Wheel));
+    }
+
+    #[test]
+    fn test_build_reads_operating_modes() {
+        let replies = [
+            status_packet(1, &[Mode::Position.code()]),
+            status_packet(2, &[Mode::Pwm.code()]),
+            status_packet(3, &[Mode::Step.code()]),
+            status_packet(2, &[]),
+            status_packet(3, &[]),
+            status_packet(1, &[]),
+        ]
+        .concat();
+        let mut robot = Robot::builder(MockPort::new(&replies))
+            .servo_ids([1, 2, 3])
+            .build()
+            .unwrap();
+
+        assert_eq!(robot.operating_mode(2), Some(Mode::Pwm));
+        assert!(matches!(
+            robot.send_absolute_move_command(1, 1000, None),
+            Err(ServoError::WrongMode(2, Mode::Pwm))
+        ));
+        assert!(matches!(
+            robot.write_pwm(3, 500),
+            Err(ServoError::WrongMode(3, Mode::Step))
+        ));
+        assert!(matches!(
+            robot.move_steps(1, 10),
+            Err(ServoError::WrongMode(1, Mode::Position))
+        ));
+        robot.write_pwm(2, 500).unwrap();
+        robot.move_steps(3, 10).unwrap();
+
+        // Reverse is only for wheel mode, a position mode speed limit may be set any time
+        assert!(matches!(
+            robot.write_goal_speed(1, -100),
+            Err(ServoError::WrongMode(1, Mode::Position))
+        ));
+        robot.write_goal_speed(1, 100).unwrap();
+    }
+
+    #[test]
+    fn test_discover_counts_every_servo() {
+        // Servos 1 and 2 answer: ping, model number, firmware major and minor version
+        let mut port = MockPort::new(&[]);
+        for id in [1, 2] {
+            for data in [&[][..], &[0x09, 0x03], &[3], &[10]] {
+                port = port.reply(&status_packet(id, data));
+            }
+        }
+
+        let robot = Robot::<_, 1>::discover(port, 1..=2);
+
+        assert!(matches!(robot, Err(ServoError::ServoCountMismatch(1, 2))));
+    }
+
+    #[test]
+    fn test_position_commands_refused_in_wheel_mode() {
+        // Unlock, mode write and lock
+        let mut robot = build_robot([1, 2], &status_packet(2, &[]).repeat(3));
+
+        robot.set_operating_mode(2, Mode::Wheel).unwrap();
+        assert_eq!(robot.operating_mode(2), Some(Mode::Wheel));
+
+        assert!(matches!(
+            robot.send_absolute_move_command(1, 1000, None),
+            Err(ServoError::WrongMode(2, Mode::Wheel))
+        ));
+        assert!(matches!(
+            robot.move_to_position(2, 1000, None),
+            Err(ServoError::WrongMode(2, Mode::Wheel))
+        ));
+        assert!(robot.send_absolute_move_command(0, 1000, None).is_ok());
+    }
+
+    #[test]
+    fn test_factory_reset_updates_servo_state() {
+        use crate::FACTORY_DEFAULT_ID;
+
+        let modes = [
+            status_packet(2, &[Mode::Position.code()]),
+            status_packet(5, &[Mode::Wheel.code()]),
+        ];
+        // Nothing answers on id 1 before the reset, then the reset reply and the servo on id 1
+        let port = MockPort::new(&modes.concat())
+            .silence()
+            .reply(&status_packet(5, &[]))
+            .reply(&status_packet(FACTORY_DEFAULT_ID, &[]));
+        let mut robot = Robot::builder(port).servo_ids([2, 5]).build().unwrap();
+
+        let confirmation = ResetConfirmation::erase_configuration_of(5);
+        assert_eq!(
+            robot.factory_reset(5, confirmation, |_| {}).unwrap(),
+            FACTORY_DEFAULT_ID
+        );
+
+        assert_eq!(robot.servo_state().servo_ids, [2, FACTORY_DEFAULT_ID]);
+        assert_eq!(robot.servo_state().infos[1].id, FACTORY_DEFAULT_ID);
+        assert_eq!(
+            robot.operating_mode(FACTORY_DEFAULT_ID),
+            Some(Mode::Position)
+        );
+    }
+
+    #[test]
+    fn test_set_compliance() {
+        let mut robot = build_robot([1, 2], &status_packet(2, &[]).repeat(2));
+
+        robot.set_compliance(1, 0.7).unwrap();
+        robot.set_compliance(1, 2.0).unwrap();
+
+        // Torque limit 300, then 0
+        assert_eq!(&robot.port.tx[2..8], &[2, 5, 0x03, 0x30, 0x2c, 0x01]);
+        assert_eq!(&robot.port.tx[11..17], &[2, 5, 0x03, 0x30, 0x00, 0x00]);
+        robot.port.tx.clear();
+        for fraction in [f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                robot.set_compliance(1, fraction),
+                Err(ServoError::InvalidCompliance)
+            ));
+        }
+        assert!(robot.port.tx.is_empty(), "The torque limit is left alone");
+    }
+
+    #[test]
+    fn test_move_to_pose_arrives_together() {
+        let mut robot = build_robot([1, 2, 3], &[]);
+        for (info, position) in robot.servo_state.infos.iter_mut().zip([1000, 2000, 3000]) {
+            info.position = position;
+        }
+
+        robot
+            .move_to_pose(&[1400, 1900, 3000], Duration::from_millis(800))
+            .unwrap();
+
+        // 400 steps and 100 steps in 0.8 s, the third joint stays put
+        let tx = &robot.port.tx;
+        assert_eq!(&tx[4..7], &[0x83, 0x29, 7]);
+        assert_eq!(&tx[7..15], &[1, 0, 0x78, 0x05, 0, 0, 0xf4, 0x01]);
+        assert_eq!(&tx[15..23], &[2, 0, 0x6c, 0x07, 0, 0, 0x7d, 0x00]);
+        assert_eq!(&tx[23..31], &[3, 0, 0xb8, 0x0b, 0, 0, 0x01, 0x00]);
+        assert_eq!(robot.servo_state().infos[0].goal_position, 1400);
+
+        robot.port.tx.clear();
+        assert!(matches!(
+            robot.move_to_pose(&[1400, 5000, 3000], Duration::from_millis(800)),
+            Err(ServoError::LimitExceeded(2, 5000))
+        ));
+        assert!(
+            robot.port.tx.is_empty(),
+            "Nothing is sent when a goal is refused"
+        );
+    }
+
+    #[test]
+    fn test_execute_trajectory() {
+        use crate::trajectory::{Profile, Waypoint};
+
+        let mut robot = build_robot([1], &[]);
+        robot.servo_state.infos[0].position = 1000;
+        let waypoints = [
+            Waypoint::new(Duration::ZERO, [1000]),
+            Waypoint::new(Duration::from_millis(100), [1100]),
+        ];
+        let trajectory = Trajectory::new(&waypoints, Profile::Linear).unwrap();
+
+        let mut delays = std::vec::Vec::new();
+        robot
+            .execute_trajectory(&trajectory, 20, |delay| delays.push(delay))
+            .unwrap();
+
+        // Setpoints at 0, 50 and 100 ms, each one a 16 byte SYNC WRITE
+        assert_eq!(
+            delays,
+            [Duration::from_millis(50); 2],
+            "No delay after the last setpoint"
+        );
+        let packets: std::vec::Vec<_> = robot.port.tx.chunks(16).collect();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(&packets[1][7..11], &[1, 0, 0x1a, 0x04], "Position 1050");
+        assert_eq!(&packets[1][13..15], &[0xe8, 0x03], "50 steps in 50 ms");
+        assert_eq!(robot.servo_state().infos[0].goal_position, 1100);
+
+        // At 8 Hz over 600 ms the last step is only 100 ms
+        let mut robot = build_robot([1], &[]);
+        robot.servo_state.infos[0].position = 1000;
+        let waypoints = [
+            Waypoint::new(Duration::ZERO, [1000]),
+            Waypoint::new(Duration::from_millis(600), [1600]),
+        ];
+        let trajectory = Trajectory::new(&waypoints, Profile::Linear).unwrap();
+
+        let mut delays = std::vec::Vec::new();
+        robot
+            .execute_trajectory(&trajectory, 8, |delay| delays.push(delay))
+            .unwrap();
+
+        assert_eq!(delays, [125, 125, 125, 125, 100].map(Duration::from_millis));
+        let last = robot.port.tx.chunks(16).last().unwrap();
+        assert_eq!(&last[7..11], &[1, 0, 0x40, 0x06], "Position 1600");
+        assert_eq!(&last[13..15], &[0xe8, 0x03], "100 steps in 100 ms");
+    }
+
+    #[test]
+    fn test_soft_limits() {
+        let mut robot = build_robot([1, 2], &[]);
+        robot.send_absolute_move_command(0, 4090, None).unwrap();
+
+        // Past the end of the range is refused instead of wrapping around to 0
+        assert!(matches!(
+            robot.send_relative_move_command(0, 20, None),
+            Err(ServoError::LimitExceeded(1, 4110))
+        ));
+        assert_eq!(robot.servo_state().infos[0].goal_position, 4090);
+
+        robot.set_soft_limits(1, 1000..=3000).unwrap();
+        assert!(matches!(
+            robot.send_absolute_move_command(1, 500, None),
+            Err(ServoError::LimitExceeded(2, 500))
+        ));
+
+        robot.set_limit_policy(LimitPolicy::Clamp);
+        robot.send_relative_move_command(0, 20, None).unwrap();
+        robot.send_absolute_move_command(1, 500, None).unwrap();
+        assert_eq!(robot.servo_state().infos[0].goal_position, 4095);
+        assert_eq!(robot.servo_state().queued_commands[1].position, 1000);
+
+        // A reversed range is refused and the previous limits stay
+        assert!(matches!(
+            robot.set_soft_limits(1, RangeInclusive::new(3000, 1000)),
+            Err(ServoError::ValueOutOfRange(_, 3000))
+        ));
+        assert_eq!(robot.servo_state().soft_limits[1], 1000..=3000);
+    }
+
+    #[test]
+    fn test_joint_index_out_of_range() {
+        let mut robot = build_robot([1, 2], &[]);
+
+        assert!(matches!(
+            robot.set_soft_limits(2, 0..=100),
+            Err(ServoError::JointNotFound(2))
+        ));
+        assert!(matches!(
+            robot.write_angle_limits(2, 0..=100),
+            Err(ServoError::JointNotFound(2))
+        ));
+        assert!(matches!(
+            robot.set_joint_offset(5, 10),
+            Err(ServoError::JointNotFound(5))
+        ));
+        assert!(matches!(
+            robot.set_compliance(2, 0.5),
+            Err(ServoError::JointNotFound(2))
+        ));
+        assert!(robot.port.tx.is_empty());
+    }
+
+    #[test]
+    fn test_write_angle_limits_by_joint() {
+        // Unlock, write and lock for each of the two limits of the second joint
+        let mut robot = build_robot([1, 2], &status_packet(2, &[]).repeat(6));
+
+        robot.write_angle_limits(1, 1000..=3000).unwrap();
+
+        assert_eq!(robot.port.tx[2], 2, "Written to the servo of joint 1");
+        assert_eq!(robot.servo_state().soft_limits[1], 1000..=3000);
+        assert_eq!(robot.servo_state().soft_limits[0], 0..=MAX_POSITION);
+    }
+
+    #[test]
+    fn test_load_reversed_soft_limits() {
+        let limits = [0xb8, 0x0b, 0xe8, 0x03]; // 3000..=1000
+        let mut robot = build_robot([1], &status_packet(1, &limits));
+        assert!(matches!(
+            robot.load_soft_limits(),
+            Err(ServoError::ValueOutOfRange(_, 3000))
+        ));
+        assert_eq!(robot.servo_state().soft_limits[0], 0..=MAX_POSITION);
+    }
+}
@@ -1,21 +1,48 @@
-use std::time::Duration;
+use std::{ops::RangeInclusive, time::Duration};
 
 use embedded_io_adapters::std::FromStd;
 use log::info;
-use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
 
-use crate::{ServoError, lerobot::robot::Robot};
+use crate::{BaudRate, ServoError, half_duplex::HalfDuplex, lerobot::robot::Robot};
 
-    pub(crate) fn new_std_robot(port_name: &str) ->Result<Robot<FromStd<Box<dyn SerialPort>>>, ServoError> {
-        let port = create_servo_port(port_name)
+/// Port timeout during normal operation.
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Port timeout while scanning the bus, where most ids do not answer.
+const SCAN_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// A serial port opened with [`serialport`], adapted to the embedded-io traits.
+pub type StdPort = FromStd<Box<dyn SerialPort>>;
+
+    pub(crate) fn new_std_robot(port_name: &str, baud_rate: BaudRate) ->Result<Robot<StdPort>, ServoError> {
+        let port = create_servo_port(port_name, baud_rate)
             .map_err(|_e| ServoError::IOError)?;
         let rw = FromStd::new(port);
         Robot::new(rw)
     }
 
-    fn create_servo_port(port_name: &str) -> Result<Box<dyn SerialPort>, serialport::Error> {
-        let port = serialport::new(port_name, 1_000_000)
-            .timeout(Duration::from_millis(1000))
+    pub(crate) fn discover_std_robot<const N: usize, const BUFFER_SIZE: usize>(port_name: &str, id_range: RangeInclusive<u8>) ->Result<Robot<StdPort, N, BUFFER_SIZE>, ServoError> {
+        let mut port = create_servo_port(port_name, BaudRate::B1000000)
+            .map_err(|_e| ServoError::IOError)?;
+        port.set_timeout(SCAN_TIMEOUT)
+            .map_err(|_e| ServoError::IOError)?;
+        let mut robot = Robot::discover(FromStd::new(port), id_range)?;
+        robot.port_mut().inner_mut().set_timeout(TIMEOUT)
+            .map_err(|_e| ServoError::IOError)?;
+        Ok(robot)
+    }
+
+    /// Opens a robot on a one-wire bus where the adapter receives its own transmissions.
+    pub(crate) fn new_std_half_duplex_robot(port_name: &str) ->Result<Robot<HalfDuplex<StdPort>>, ServoError> {
+        let port = create_servo_port(port_name, BaudRate::B1000000)
+            .map_err(|_e| ServoError::IOError)?;
+        Robot::new(HalfDuplex::new(FromStd::new(port)))
+    }
+
+    pub fn create_servo_port(port_name: &str, baud_rate: BaudRate) -> Result<Box<dyn SerialPort>, serialport::Error> {
+        let port = serialport::new(port_name, baud_rate.bits_per_second())
+            .timeout(TIMEOUT)
             .data_bits(DataBits::Eight)
             .stop_bits(StopBits::One)
             .parity(Parity::None)
@@ -24,4 +51,14 @@ use crate::{ServoError, lerobot::robot::Robot};
 
         info!("Port opened successfully: {}", port_name);
         Ok(port)
-    }
\ No newline at end of file
+    }
+
+    /// Switches an open port to another baud rate and drops any bytes received at the old rate.
+    /// Fits the `reconfigure_port` argument of [`crate::set_servo_baud`] and
+    /// [`crate::detect_baud_rate`].
+    pub fn set_port_baud(port: &mut StdPort, baud_rate: BaudRate) -> Result<(), ServoError> {
+        let port = port.inner_mut();
+        port.set_baud_rate(baud_rate.bits_per_second())
+            .and_then(|_| port.clear(ClearBuffer::Input))
+            .map_err(|_e| ServoError::IOError)
+    }
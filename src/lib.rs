@@ -1,25 +1,63 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use core::{
+    ops::{Range, RangeInclusive},
+    time::Duration,
+};
+
 use embedded_io::{Read, Write};
+use log::info;
 
-use crate::comm::{
-    CURRENT_REGISTER, Command, LOAD_REGISTER, MOVING_REGISTER, POSITION_REGISTER, SPEED_REGISTER,
-    STATUS_REGISTER, TEMPERATURE_REGISTER, VOLTAGE_REGISTER, send_ping, write_position,
+use crate::{
+    comm::{
+        ACCELERATION_REGISTER, CURRENT_REGISTER, Command, CommandResponse, GOAL_POSITION_REGISTER,
+        LOAD_REGISTER, MOVE_BLOCK_LENGTH, MOVING_REGISTER, POSITION_REGISTER, SPEED_REGISTER,
+        STATUS_REGISTER, TEMPERATURE_REGISTER, VOLTAGE_REGISTER, move_block, send_action,
+        send_ping, send_reset, write_position,
+    },
+    registers::{
+        FirmwareMajorVersion, FirmwareMinorVersion, GoalPosition, GoalSpeed, GoalTime, Id,
+        LOAD_SIGN_BIT, Lock, MaxAngleLimit, MinAngleLimit, ModelNumber, OFFSET_SIGN_BIT,
+        PWM_SIGN_BIT, PositionOffset, Register, RegisterValue, SPEED_SIGN_BIT, STEP_SIGN_BIT,
+        TorqueEnable, TorqueLimit, Writable, decode_sign_magnitude, encode_sign_magnitude,
+    },
+    units::{Angle, AngularVelocity, Current, Voltage},
 };
 
 mod comm;
+mod frame;
+pub mod half_duplex;
+#[cfg(test)]
+mod mock;
 
 #[cfg(feature = "ui")]
 pub mod info;
 
 pub mod lerobot;
+pub mod registers;
+pub mod trajectory;
+pub mod tuning;
+pub mod units;
+
+pub use comm::BROADCAST_ID;
+
+/// The ID a servo answers on after a factory reset.
+pub const FACTORY_DEFAULT_ID: u8 = 1;
 
-// const REG_WRITE_ID: u8 = 0x04;
-// const ACTION_ID: u8 = 0x05;
-// const RESET_ID: u8 = 0x06;
-// const SYNC_WRITE_ID: u8 = 0x83;
-// const SYNC_READ_ID: u8 = 0x82;
-// const BROADCAST_ID: u8 = 0xfe; //?
+/// Number of pings per candidate ID while waiting for a reset servo to come back.
+const RESET_PING_ATTEMPTS: usize = 3;
+
+/// Time a servo takes to reboot after a RESET, waited before each round of pings.
+const RESET_REBOOT_TIME: Duration = Duration::from_millis(500);
+
+/// Largest SYNC WRITE payload that still fits the one byte packet length.
+const MAX_SYNC_WRITE_PAYLOAD: usize = 251;
+
+/// Largest register block a single servo can return from [`sync_read`].
+pub const MAX_SYNC_READ_LENGTH: usize = 32;
+
+/// Register bytes returned by one servo in a [`sync_read`].
+pub type RegisterBlock = heapless::Vec<u8, MAX_SYNC_READ_LENGTH>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ServoError {
@@ -28,17 +66,239 @@ pub enum ServoError {
     #[error("Serial port read error")]
     ReadError,
     #[error("Servo returned error status: {0}")]
-    StatusError(u8),
+    StatusError(ServoStatus),
     #[error("Failed to parse servo response")]
     ResponseParseError,
+    #[error("Timed out waiting for a response")]
+    Timeout,
+    #[error("Response ended before the end of the packet")]
+    ShortFrame,
+    #[error("Packet length byte out of range: {0}")]
+    LengthOutOfRange(u8),
+    #[error("Echo of the transmitted packet did not match")]
+    EchoMismatch,
     #[error("Invalid header bytes: {0:#X}, {1:#X}")]
     InvalidHeader(u8, u8),
     #[error("Checksum mismatch: calculated {0:#X}, received {1:#X}")]
     ChecksumMismatch(u8, u8),
+    #[error("Response id mismatch: expected servo {0}, received servo {1}")]
+    IdMismatch(u8, u8),
     #[error("Command overflow")]
     CommandOverflow,
     #[error("IO Error")]
     IOError,
+    #[error("Value {1} out of range for register {0:#04X}")]
+    ValueOutOfRange(u8, u16),
+    #[error("Servo id {0} is already in use")]
+    IdInUse(u8),
+    #[error("Expected {0} servos, found {1}")]
+    ServoCountMismatch(usize, usize),
+    #[error("Servo {0} did not respond")]
+    ServoNotFound(u8),
+    #[error("No joint at index {0}")]
+    JointNotFound(usize),
+    #[error("Reset of servo {0} was not confirmed")]
+    ResetNotConfirmed(u8),
+    #[error("Servo {0} is in {1:?} mode")]
+    WrongMode(u8, Mode),
+    #[error("Operating mode of servo {0} is unknown")]
+    UnknownMode(u8),
+    #[error("Goal position {1} of servo {0} is outside its limits")]
+    LimitExceeded(u8, i32),
+    #[error("Servo {0} did not keep the written configuration")]
+    VerificationFailed(u8),
+    #[error("Trajectory needs waypoints with increasing times and positive limits")]
+    InvalidTrajectory,
+    #[error("Joint {0} cannot follow the trajectory within its limits")]
+    TrajectoryLimitExceeded(usize),
+    #[error("Compliance must be a finite fraction")]
+    InvalidCompliance,
+}
+
+/// Serial speeds supported by the servo, with their baud rate register codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BaudRate {
+    B1000000 = 0,
+    B500000 = 1,
+    B250000 = 2,
+    B128000 = 3,
+    B115200 = 4,
+    B76800 = 5,
+    B57600 = 6,
+    B38400 = 7,
+}
+
+impl BaudRate {
+    /// All rates, fastest first, in the order [`detect_baud_rate`] tries them.
+    pub const ALL: [BaudRate; 8] = [
+        BaudRate::B1000000,
+        BaudRate::B500000,
+        BaudRate::B250000,
+        BaudRate::B128000,
+        BaudRate::B115200,
+        BaudRate::B76800,
+        BaudRate::B57600,
+        BaudRate::B38400,
+    ];
+
+    pub fn bits_per_second(self) -> u32 {
+        match self {
+            BaudRate::B1000000 => 1_000_000,
+            BaudRate::B500000 => 500_000,
+            BaudRate::B250000 => 250_000,
+            BaudRate::B128000 => 128_000,
+            BaudRate::B115200 => 115_200,
+            BaudRate::B76800 => 76_800,
+            BaudRate::B57600 => 57_600,
+            BaudRate::B38400 => 38_400,
+        }
+    }
+
+    /// The value of the baud rate register for this rate.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+}
+
+/// Operating modes of the servo, with their mode register codes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Mode {
+    /// Moves to absolute goal positions.
+    #[default]
+    Position = 0,
+    /// Turns continuously at the goal speed, see [`write_goal_speed`].
+    Wheel = 1,
+    /// Drives the motor with an open-loop PWM output, see [`write_pwm`].
+    Pwm = 2,
+    /// Moves by a relative number of steps, see [`move_steps`].
+    Step = 3,
+}
+
+impl Mode {
+    /// The value of the mode register for this mode.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Wheel),
+            2 => Some(Mode::Pwm),
+            3 => Some(Mode::Step),
+            _ => None,
+        }
+    }
+}
+
+/// Torque enable value that calibrates the current position as the mid-point.
+const SET_MIDDLE_POSITION: u8 = 128;
+
+/// How a servo travels to its goal position. Accelerations go up to 254; moves with a larger
+/// one fail with [`ServoError::ValueOutOfRange`] before anything is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveProfile {
+    /// Cruises at `speed` steps/s, ramping up and down with `acceleration` in units of
+    /// 100 steps/s². An acceleration of 0 ramps as fast as the servo can.
+    BySpeed { speed: u16, acceleration: u8 },
+    /// Arrives after `millis` ms; the servo works out the speed.
+    ByTime { millis: u16 },
+    /// Ramps up and down with `acceleration` in units of 100 steps/s², cruising at full speed.
+    ByAcceleration { acceleration: u8 },
+}
+
+impl MoveProfile {
+    /// Cruises at `velocity`, see [`MoveProfile::BySpeed`]. The direction of `velocity` is
+    /// ignored.
+    pub fn at_velocity(velocity: AngularVelocity, acceleration: u8) -> Self {
+        MoveProfile::BySpeed {
+            speed: velocity.to_steps_per_second().unsigned_abs(),
+            acceleration,
+        }
+    }
+
+    /// The acceleration, goal time and goal speed registers for this profile.
+    pub(crate) fn registers(self) -> (u8, u16, u16) {
+        match self {
+            MoveProfile::BySpeed {
+                speed,
+                acceleration,
+            } => (acceleration, 0, speed),
+            MoveProfile::ByTime { millis } => (0, millis, 0),
+            MoveProfile::ByAcceleration { acceleration } => (acceleration, 0, 0),
+        }
+    }
+}
+
+/// Goal position of one servo in a [`sync_write_positions`].
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ServoPositionCommand {
+    pub id: u8,
+    pub position: u16,
+    /// How to get there; `None` keeps the acceleration, time and speed the servo has, also in a
+    /// [`sync_write_positions`] next to commands that have a profile.
+    pub profile: Option<MoveProfile>,
+}
+
+/// Largest PWM output accepted by [`write_pwm`], in 0.1% of the maximum output.
+pub const MAX_PWM: u16 = 1000;
+
+/// A servo that answered during [`scan_bus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredServo {
+    pub id: u8,
+    pub model_number: u16,
+    pub firmware_major: u8,
+    pub firmware_minor: u8,
+}
+
+bitflags::bitflags! {
+    /// Fault bits reported by a servo, both in the error byte of every status packet and in the
+    /// status register.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct ServoStatus: u8 {
+        const VOLTAGE = 1 << 0;
+        const SENSOR = 1 << 1;
+        const OVERHEAT = 1 << 2;
+        const OVER_CURRENT = 1 << 3;
+        const ANGLE_LIMIT = 1 << 4;
+        const OVERLOAD = 1 << 5;
+    }
+}
+
+impl core::fmt::Display for ServoStatus {
+    /// Lists the active faults, e.g. `overheat, overload`, or `none`.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const LABELS: [(ServoStatus, &str); 6] = [
+            (ServoStatus::VOLTAGE, "voltage"),
+            (ServoStatus::SENSOR, "sensor"),
+            (ServoStatus::OVERHEAT, "overheat"),
+            (ServoStatus::OVER_CURRENT, "over-current"),
+            (ServoStatus::ANGLE_LIMIT, "angle limit"),
+            (ServoStatus::OVERLOAD, "overload"),
+        ];
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        let mut separator = "";
+        for (flag, label) in LABELS {
+            if self.contains(flag) {
+                write!(f, "{separator}{label}")?;
+                separator = ", ";
+            }
+        }
+        let unknown = self.bits() & !ServoStatus::all().bits();
+        if unknown != 0 {
+            write!(f, "{separator}unknown {unknown:#04X}")?;
+        }
+        Ok(())
+    }
 }
 
 pub fn read_temperature<P: Write + Read>(
@@ -81,6 +341,14 @@ pub fn has_error<P: Write + Read>(
     read_u8_register(port, buffer, servo_id, STATUS_REGISTER).map(|value| value != 0)
 }
 
+pub fn read_status<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<ServoStatus, ServoError> {
+    read_u8_register(port, buffer, servo_id, STATUS_REGISTER).map(ServoStatus::from_bits_retain)
+}
+
 pub fn read_position<P: Write + Read>(
     port: &mut P,
     buffer: &mut [u8],
@@ -89,20 +357,199 @@ pub fn read_position<P: Write + Read>(
     read_u16_register(port, buffer, servo_id, POSITION_REGISTER)
 }
 
+/// Present speed in steps/s, negative when turning in reverse.
 pub fn read_speed<P: Write + Read>(
     port: &mut P,
     buffer: &mut [u8],
     servo_id: u8,
-) -> Result<u16, ServoError> {
+) -> Result<i16, ServoError> {
     read_u16_register(port, buffer, servo_id, SPEED_REGISTER)
+        .map(|raw| decode_sign_magnitude(raw, SPEED_SIGN_BIT))
 }
 
+/// Present load in ‰ of the maximum torque, negative when loaded in reverse.
 pub fn read_load<P: Write + Read>(
     port: &mut P,
     buffer: &mut [u8],
     servo_id: u8,
-) -> Result<u16, ServoError> {
+) -> Result<i16, ServoError> {
     read_u16_register(port, buffer, servo_id, LOAD_REGISTER)
+        .map(|raw| decode_sign_magnitude(raw, LOAD_SIGN_BIT))
+}
+
+pub fn read_angle<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<Angle, ServoError> {
+    read_position(port, buffer, servo_id).map(Angle::from_steps)
+}
+
+pub fn read_angular_velocity<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<AngularVelocity, ServoError> {
+    read_speed(port, buffer, servo_id).map(AngularVelocity::from_steps_per_second)
+}
+
+pub fn read_supply_voltage<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<Voltage, ServoError> {
+    read_voltage(port, buffer, servo_id).map(Voltage::from_raw)
+}
+
+pub fn read_current_draw<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<Current, ServoError> {
+    read_current(port, buffer, servo_id).map(Current::from_raw)
+}
+
+/// Writes the goal speed in steps/s. In wheel mode the sign selects the direction of rotation.
+pub fn write_goal_speed<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    speed: i16,
+) -> Result<(), ServoError> {
+    write_register::<GoalSpeed, _>(
+        port,
+        buffer,
+        servo_id,
+        encode_sign_magnitude(speed, SPEED_SIGN_BIT),
+    )
+}
+
+/// Reads the angle limits the servo enforces itself, as a range of positions.
+pub fn read_angle_limits<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<RangeInclusive<u16>, ServoError> {
+    let response = Command::Read(servo_id, MinAngleLimit::ADDRESS, 4).send_command(port, buffer)?;
+    match response.data() {
+        [min_low, min_high, max_low, max_high] => {
+            Ok(u16::from_le_bytes([*min_low, *min_high])
+                ..=u16::from_le_bytes([*max_low, *max_high]))
+        }
+        _ => Err(ServoError::ResponseParseError),
+    }
+}
+
+/// Stores the angle limits of a servo in EEPROM. The servo refuses to move outside them.
+pub fn write_angle_limits<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    limits: RangeInclusive<u16>,
+) -> Result<(), ServoError> {
+    if limits.start() >= limits.end() {
+        return Err(ServoError::ValueOutOfRange(
+            MinAngleLimit::ADDRESS,
+            *limits.start(),
+        ));
+    }
+    write_eeprom_register::<MinAngleLimit, _>(port, buffer, servo_id, *limits.start())?;
+    write_eeprom_register::<MaxAngleLimit, _>(port, buffer, servo_id, *limits.end())
+}
+
+/// Largest position correction accepted by [`write_position_offset`], in steps either way.
+pub const MAX_POSITION_OFFSET: u16 = 2047;
+
+/// Stores a position correction in EEPROM: the servo reports and moves to positions shifted by
+/// `offset` steps. Fails with [`ServoError::ValueOutOfRange`] beyond [`MAX_POSITION_OFFSET`].
+pub fn write_position_offset<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    offset: i16,
+) -> Result<(), ServoError> {
+    if offset.unsigned_abs() > MAX_POSITION_OFFSET {
+        return Err(ServoError::ValueOutOfRange(
+            PositionOffset::ADDRESS,
+            offset.unsigned_abs(),
+        ));
+    }
+    let raw = encode_sign_magnitude(offset, OFFSET_SIGN_BIT);
+    write_eeprom_register::<PositionOffset, _>(port, buffer, servo_id, raw)
+}
+
+pub fn read_position_offset<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<i16, ServoError> {
+    read_register::<PositionOffset, _>(port, buffer, servo_id)
+        .map(|raw| decode_sign_magnitude(raw, OFFSET_SIGN_BIT))
+}
+
+/// Makes the current position of a servo its mid-point of 2048. The servo works out the
+/// position correction and stores it itself.
+pub fn set_middle_position<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<(), ServoError> {
+    write_register::<TorqueEnable, _>(port, buffer, servo_id, SET_MIDDLE_POSITION)
+}
+
+/// Switches a servo to another operating mode and stores it in EEPROM.
+pub fn set_operating_mode<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    mode: Mode,
+) -> Result<(), ServoError> {
+    write_eeprom_register::<registers::OperatingMode, _>(port, buffer, servo_id, mode.code())
+}
+
+pub fn read_operating_mode<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<Mode, ServoError> {
+    let code = read_register::<registers::OperatingMode, _>(port, buffer, servo_id)?;
+    Mode::from_code(code).ok_or(ServoError::ResponseParseError)
+}
+
+/// Sets the PWM output of a servo in PWM mode, in 0.1% of the maximum output; the sign selects
+/// the direction. Fails with [`ServoError::ValueOutOfRange`] beyond [`MAX_PWM`].
+pub fn write_pwm<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    pwm: i16,
+) -> Result<(), ServoError> {
+    if pwm.unsigned_abs() > MAX_PWM {
+        return Err(ServoError::ValueOutOfRange(
+            GoalTime::ADDRESS,
+            pwm.unsigned_abs(),
+        ));
+    }
+    write_register::<GoalTime, _>(
+        port,
+        buffer,
+        servo_id,
+        encode_sign_magnitude(pwm, PWM_SIGN_BIT),
+    )
+}
+
+/// Moves a servo in step mode by `steps` from where it is, negative steps turning in reverse.
+pub fn move_steps<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    steps: i16,
+) -> Result<(), ServoError> {
+    // The sign bit is outside the absolute position range write_register checks against
+    let bytes = encode_sign_magnitude(steps, STEP_SIGN_BIT).to_le_bytes();
+    Command::Write(servo_id, GoalPosition::ADDRESS, &bytes)
+        .send_command(port, buffer)?
+        .is_error()
 }
 
 pub fn read_u8_register<P: Write + Read>(
@@ -125,16 +572,159 @@ pub fn read_u16_register<P: Write + Read>(
     result.data_as_u16().ok_or(ServoError::ReadError)
 }
 
+/// Reads a register of the control table, e.g. `read_register::<registers::PresentPosition>`.
+pub fn read_register<R: Register, P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<R::Value, ServoError> {
+    let raw = match R::Value::WIDTH {
+        1 => read_u8_register(port, buffer, servo_id, R::ADDRESS)?.into(),
+        _ => read_u16_register(port, buffer, servo_id, R::ADDRESS)?,
+    };
+    Ok(R::Value::from_raw(raw))
+}
+
+/// Writes a register of the control table. Values outside the register's range are rejected
+/// without writing.
+///
+/// EEPROM registers only keep the value across power cycles while the EEPROM is unlocked.
+pub fn write_register<R: Writable, P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    value: R::Value,
+) -> Result<(), ServoError> {
+    check_range::<R>(value)?;
+    let bytes = value.into().to_le_bytes();
+    Command::Write(servo_id, R::ADDRESS, &bytes[..R::Value::WIDTH as usize])
+        .send_command(port, buffer)?
+        .is_error()
+}
+
+/// Fails with [`ServoError::ValueOutOfRange`] when the servo would not accept `value` for `R`.
+pub(crate) fn check_range<R: Register>(value: R::Value) -> Result<(), ServoError> {
+    if value < R::MIN || value > R::MAX {
+        return Err(ServoError::ValueOutOfRange(R::ADDRESS, value.into()));
+    }
+    Ok(())
+}
+
+/// Writes an EEPROM register so the value survives a power cycle: unlocks the EEPROM, writes the
+/// value and locks it again. The EEPROM is relocked even when the write fails.
+pub fn write_eeprom_register<R: Writable, P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    value: R::Value,
+) -> Result<(), ServoError> {
+    write_register::<Lock, _>(port, buffer, servo_id, 0)?;
+    let written = write_register::<R, _>(port, buffer, servo_id, value);
+    let locked = write_register::<Lock, _>(port, buffer, servo_id, 1);
+    written.and(locked)
+}
+
+/// Changes the id of a servo and stores it in EEPROM.
+///
+/// Refuses with [`ServoError::IdInUse`] when a servo already answers on `new_id`, and verifies
+/// the change by pinging the servo on its new id. Refuses a broadcast `old_id` with
+/// [`ServoError::ValueOutOfRange`], as every servo on the bus would get `new_id`.
+pub fn set_servo_id<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    old_id: u8,
+    new_id: u8,
+) -> Result<(), ServoError> {
+    if old_id == BROADCAST_ID {
+        return Err(ServoError::ValueOutOfRange(
+            Id::ADDRESS,
+            BROADCAST_ID as u16,
+        ));
+    }
+    if old_id == new_id {
+        return ping_servo(port, buffer, old_id);
+    }
+    if ping_servo(port, buffer, new_id).is_ok() {
+        return Err(ServoError::IdInUse(new_id));
+    }
+
+    write_register::<Lock, _>(port, buffer, old_id, 0)?;
+    match write_register::<Id, _>(port, buffer, old_id, new_id) {
+        // The servo may already answer with its new id
+        Ok(()) | Err(ServoError::IdMismatch(_, _)) => {}
+        Err(e) => {
+            let _ = write_register::<Lock, _>(port, buffer, old_id, 1);
+            return Err(e);
+        }
+    }
+    write_register::<Lock, _>(port, buffer, new_id, 1)?;
+
+    ping_servo(port, buffer, new_id).map_err(|_| ServoError::ServoNotFound(new_id))?;
+    info!("Changed servo id {} to {}", old_id, new_id);
+    Ok(())
+}
+
+/// Switches a servo to another baud rate and stores it in EEPROM.
+///
+/// After the baud rate register is written the servo only listens at the new rate, so
+/// `reconfigure_port` is called to switch the port before the EEPROM is relocked and the servo is
+/// pinged to verify the change. Other servos on the bus keep their rate.
+pub fn set_servo_baud<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    baud_rate: BaudRate,
+    mut reconfigure_port: impl FnMut(&mut P, BaudRate) -> Result<(), ServoError>,
+) -> Result<(), ServoError> {
+    write_register::<Lock, _>(port, buffer, servo_id, 0)?;
+    match write_register::<registers::BaudRate, _>(port, buffer, servo_id, baud_rate.code()) {
+        // The reply may already be sent at the new rate and arrive garbled
+        Ok(())
+        | Err(ServoError::Timeout)
+        | Err(ServoError::ShortFrame)
+        | Err(ServoError::ChecksumMismatch(_, _))
+        | Err(ServoError::LengthOutOfRange(_)) => {}
+        Err(e) => return Err(e),
+    }
+    reconfigure_port(port, baud_rate)?;
+    write_register::<Lock, _>(port, buffer, servo_id, 1)?;
+    ping_servo(port, buffer, servo_id).map_err(|_| ServoError::ServoNotFound(servo_id))?;
+    info!(
+        "Servo {} now runs at {} baud",
+        servo_id,
+        baud_rate.bits_per_second()
+    );
+    Ok(())
+}
+
+/// Finds the baud rate a servo listens at by switching the port through every supported rate
+/// and pinging the servo. The port is left at the detected rate.
+pub fn detect_baud_rate<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    mut reconfigure_port: impl FnMut(&mut P, BaudRate) -> Result<(), ServoError>,
+) -> Result<BaudRate, ServoError> {
+    for baud_rate in BaudRate::ALL {
+        reconfigure_port(port, baud_rate)?;
+        if ping_servo(port, buffer, servo_id).is_ok() {
+            info!(
+                "Found servo {} at {} baud",
+                servo_id,
+                baud_rate.bits_per_second()
+            );
+            return Ok(baud_rate);
+        }
+    }
+    Err(ServoError::ServoNotFound(servo_id))
+}
+
 pub fn enable_torque<P: Write + Read>(
     port: &mut P,
     buffer: &mut [u8],
     servo_id: u8,
 ) -> Result<(), ServoError> {
-    let torque_enable_addr = 0x30;
-    let enable_value = [0x01]; // 1 to enable torque
-    Command::Write(servo_id, torque_enable_addr, &enable_value)
-        .send_command(port, buffer)
-        .and_then(|response| response.is_error())
+    write_register::<TorqueEnable, _>(port, buffer, servo_id, 1)
 }
 
 pub fn disable_torque<P: Write + Read>(
@@ -142,11 +732,40 @@ pub fn disable_torque<P: Write + Read>(
     buffer: &mut [u8],
     servo_id: u8,
 ) -> Result<(), ServoError> {
-    let torque_enable_addr = 0x30;
-    let enable_value = [0x00]; // 0 to disable torque
-    Command::Write(servo_id, torque_enable_addr, &enable_value)
-        .send_command(port, buffer)
-        .and_then(|response| response.is_error())
+    write_register::<TorqueEnable, _>(port, buffer, servo_id, 0)
+}
+
+/// Limits the torque a servo applies, in 0.1% of the maximum torque. Unlike [`disable_torque`]
+/// the servo keeps holding its goal, but can be pushed away from it by a larger force. The limit
+/// returns to [`registers::MaxTorqueLimit`] on power up.
+pub fn write_torque_limit<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    limit: u16,
+) -> Result<(), ServoError> {
+    write_register::<TorqueLimit, _>(port, buffer, servo_id, limit)
+}
+
+pub fn read_torque_limit<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+) -> Result<u16, ServoError> {
+    read_register::<TorqueLimit, _>(port, buffer, servo_id)
+}
+
+/// Disables torque on every servo on the bus with a single broadcast packet, e.g. as an
+/// emergency stop. Servos do not reply, so delivery is not confirmed.
+pub fn broadcast_disable_torque<P: Write + Read>(port: &mut P) -> Result<(), ServoError> {
+    let mut buffer = [0u8; 8];
+    disable_torque(port, &mut buffer, BROADCAST_ID)
+}
+
+/// Enables torque on every servo on the bus with a single broadcast packet.
+pub fn broadcast_enable_torque<P: Write + Read>(port: &mut P) -> Result<(), ServoError> {
+    let mut buffer = [0u8; 8];
+    enable_torque(port, &mut buffer, BROADCAST_ID)
 }
 
 pub fn move_to_position<P: Write + Read>(
@@ -154,10 +773,205 @@ pub fn move_to_position<P: Write + Read>(
     buffer: &mut [u8],
     servo_id: u8,
     position: u16,
-    speed: Option<u16>,
-    acc: Option<u16>,
+    profile: Option<MoveProfile>,
+) -> Result<(), ServoError> {
+    write_position(port, buffer, servo_id, position, profile)?.is_error()
+}
+
+/// Moves a servo to `angle`.
+pub fn move_to_angle<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    angle: Angle,
+    profile: Option<MoveProfile>,
+) -> Result<(), ServoError> {
+    move_to_position(port, buffer, servo_id, angle.to_steps(), profile)
+}
+
+/// Sends the goal positions of several servos with SYNC WRITE.
+///
+/// The servos do not reply to a SYNC WRITE, so this returns as soon as the packets are written.
+/// Commands with a [`MoveProfile`] share one packet that also writes acceleration, time and
+/// speed; commands without one share a second packet that only writes the goal position, so
+/// those servos keep their own profile.
+pub fn sync_write_positions<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    commands: &[ServoPositionCommand],
+) -> Result<(), ServoError> {
+    let profiled = commands.iter().filter(|command| command.profile.is_some());
+    sync_write_move_blocks(
+        port,
+        buffer,
+        profiled,
+        ACCELERATION_REGISTER,
+        0..MOVE_BLOCK_LENGTH,
+    )?;
+    let unprofiled = commands.iter().filter(|command| command.profile.is_none());
+    sync_write_move_blocks(port, buffer, unprofiled, GOAL_POSITION_REGISTER, 1..3)
+}
+
+/// Sends `range` of the move block of every command, starting at register `address`, in one
+/// SYNC WRITE. Sends nothing when there are no commands.
+fn sync_write_move_blocks<'c, P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    commands: impl Iterator<Item = &'c ServoPositionCommand>,
+    address: u8,
+    range: Range<usize>,
+) -> Result<(), ServoError> {
+    let mut payload: heapless::Vec<u8, MAX_SYNC_WRITE_PAYLOAD> = heapless::Vec::new();
+    for command in commands {
+        let block = move_block(command.position, command.profile)?;
+        payload
+            .push(command.id)
+            .map_err(|_| ServoError::CommandOverflow)?;
+        payload
+            .extend_from_slice(&block[range.clone()])
+            .map_err(|_| ServoError::CommandOverflow)?;
+    }
+    if payload.is_empty() {
+        return Ok(());
+    }
+    Command::SyncWrite(address, range.len() as u8, &payload).send_without_response(port, buffer)
+}
+
+/// Reads `length` bytes starting at `start_addr` from every servo in `servo_ids` with a single
+/// SYNC READ packet.
+///
+/// The outer error reports a failure to send the request. Each servo answers with its own status
+/// packet, so the returned array holds one result per servo, in the order of `servo_ids`.
+pub fn sync_read<P: Write + Read, const N: usize>(
+    port: &mut P,
+    buffer: &mut [u8],
+    start_addr: u8,
+    length: u8,
+    servo_ids: &[u8; N],
+) -> Result<[Result<RegisterBlock, ServoError>; N], ServoError> {
+    if length as usize > MAX_SYNC_READ_LENGTH {
+        return Err(ServoError::CommandOverflow);
+    }
+    Command::SyncRead(start_addr, length, servo_ids)
+        .send_command_multi_response(&mut *port, buffer)?;
+
+    // Servos that never answer keep the read error
+    let mut replies: [Result<RegisterBlock, ServoError>; N] =
+        core::array::from_fn(|_| Err(ServoError::ReadError));
+    let mut next = 0;
+    while next < N {
+        let response = match CommandResponse::receive(&mut *port, buffer) {
+            Ok(response) => response,
+            Err(error) => {
+                replies[next] = Err(error);
+                next += 1;
+                continue;
+            }
+        };
+        // Replies arrive in request order, so a later id means the servos in between are silent
+        match servo_ids[next..].iter().position(|&id| id == response.id()) {
+            Some(offset) => {
+                replies[next + offset] = response
+                    .data()
+                    .get(..length as usize)
+                    .and_then(|data| RegisterBlock::from_slice(data).ok())
+                    .ok_or(ServoError::ReadError);
+                next += offset + 1;
+            }
+            None => {
+                info!(
+                    "Sync read reply from servo {} while expecting servo {}",
+                    response.id(),
+                    servo_ids[next]
+                );
+                replies[next] = Err(ServoError::IdMismatch(servo_ids[next], response.id()));
+                next += 1;
+            }
+        }
+    }
+    Ok(replies)
+}
+
+/// Stages a goal position on a servo without starting the move. Staged moves on all servos are
+/// started at the same instant by [`action`].
+pub fn reg_write_position<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    position: u16,
+    profile: Option<MoveProfile>,
 ) -> Result<(), ServoError> {
-    write_position(port, buffer, servo_id, position, speed, acc)?.is_error()
+    comm::reg_write_position(port, buffer, servo_id, position, profile)?.is_error()
+}
+
+/// Broadcasts ACTION, starting the moves staged with [`reg_write_position`] on every servo.
+pub fn action<P: Write + Read>(port: &mut P) -> Result<(), ServoError> {
+    send_action(port)
+}
+
+/// Restores the factory configuration of a servo, which also sets its ID back to
+/// [`FACTORY_DEFAULT_ID`].
+///
+/// Fails with [`ServoError::IdInUse`] before resetting when another servo already answers on
+/// [`FACTORY_DEFAULT_ID`], as the two would collide. `delay` is called to give the servo time to
+/// reboot before each round of pings, e.g. `std::thread::sleep`.
+///
+/// Returns the ID the servo answers pings on afterwards.
+pub fn reset_servo<P: Write + Read>(
+    port: &mut P,
+    buffer: &mut [u8],
+    servo_id: u8,
+    mut delay: impl FnMut(Duration),
+) -> Result<u8, ServoError> {
+    if servo_id != FACTORY_DEFAULT_ID && ping_servo(port, buffer, FACTORY_DEFAULT_ID).is_ok() {
+        return Err(ServoError::IdInUse(FACTORY_DEFAULT_ID));
+    }
+    send_reset(port, buffer, servo_id)?.is_error()?;
+    info!("Reset servo {}, waiting for it to come back", servo_id);
+    for _ in 0..RESET_PING_ATTEMPTS {
+        delay(RESET_REBOOT_TIME);
+        for candidate in [FACTORY_DEFAULT_ID, servo_id] {
+            if ping_servo(port, buffer, candidate).is_ok() {
+                info!("Servo {} came back as servo {}", servo_id, candidate);
+                return Ok(candidate);
+            }
+        }
+    }
+    Err(ServoError::ServoNotFound(servo_id))
+}
+
+/// Pings every id in `id_range` and reads the model number and firmware version of each servo
+/// that answers.
+///
+/// Every silent id costs one port timeout, so use a short timeout while scanning. Fails with
+/// [`ServoError::CommandOverflow`] when more than `N` servos answer.
+pub fn scan_bus<P: Write + Read, const N: usize>(
+    port: &mut P,
+    buffer: &mut [u8],
+    id_range: RangeInclusive<u8>,
+) -> Result<heapless::Vec<DiscoveredServo, N>, ServoError> {
+    let mut found = heapless::Vec::new();
+    for id in id_range.filter(|&id| id != BROADCAST_ID) {
+        if ping_servo(port, buffer, id).is_err() {
+            continue;
+        }
+        let servo = read_register::<ModelNumber, _>(port, buffer, id).and_then(|model_number| {
+            Ok(DiscoveredServo {
+                id,
+                model_number,
+                firmware_major: read_register::<FirmwareMajorVersion, _>(port, buffer, id)?,
+                firmware_minor: read_register::<FirmwareMinorVersion, _>(port, buffer, id)?,
+            })
+        });
+        match servo {
+            Ok(servo) => {
+                info!("Found servo {:?}", servo);
+                found.push(servo).map_err(|_| ServoError::CommandOverflow)?;
+            }
+            Err(e) => info!("Servo {} answered a ping but not a read: {:?}", id, e),
+        }
+    }
+    Ok(found)
 }
 
 pub fn ping_servo<P: Write + Read>(
@@ -171,6 +985,7 @@ pub fn ping_servo<P: Write + Read>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::{MockPort, status_packet};
 
     #[test]
     fn test_command() {
@@ -179,4 +994,287 @@ mod tests {
         let len = cmd.write_buffer(&mut buffer);
         println!("Command buffer: {:02X?}", &buffer[..len]);
     }
+
+    #[test]
+    fn test_sync_read_reports_per_servo_results() {
+        // Servo 1 and 3 reply, servo 2 is missing from the bus
+        let mut rx = status_packet(1, &[0x00, 0x08]);
+        rx.extend(status_packet(3, &[0xff, 0x0f]));
+        let mut port = MockPort::new(&rx);
+        let mut buffer = [0u8; 64];
+
+        let replies = sync_read(&mut port, &mut buffer, POSITION_REGISTER, 2, &[1, 2, 3]).unwrap();
+
+        assert_eq!(
+            &port.tx[..8],
+            &[0xff, 0xff, 0xfe, 7, 0x82, POSITION_REGISTER, 2, 1]
+        );
+        assert_eq!(replies[0].as_ref().unwrap().as_slice(), &[0x00, 0x08]);
+        assert!(replies[1].is_err(), "Servo 2 did not reply");
+        assert_eq!(replies[2].as_ref().unwrap().as_slice(), &[0xff, 0x0f]);
+    }
+
+    #[test]
+    fn test_sync_write_positions_with_profile() {
+        let commands = [
+            ServoPositionCommand {
+                id: 1,
+                position: 2048,
+                profile: Some(MoveProfile::ByTime { millis: 800 }),
+            },
+            ServoPositionCommand {
+                id: 2,
+                position: 1024,
+                profile: None,
+            },
+        ];
+        let mut port = MockPort::new(&[]);
+        let mut buffer = [0u8; 64];
+
+        sync_write_positions(&mut port, &mut buffer, &commands).unwrap();
+
+        // The profiled command writes its whole move block
+        assert_eq!(&port.tx[4..7], &[0x83, ACCELERATION_REGISTER, 7]);
+        assert_eq!(&port.tx[7..15], &[1, 0, 0x00, 0x08, 0x20, 0x03, 0, 0]);
+        // The other one only its goal position, in a second packet
+        assert_eq!(&port.tx[16..19], &[0xff, 0xff, 0xfe]);
+        assert_eq!(&port.tx[20..23], &[0x83, GOAL_POSITION_REGISTER, 2]);
+        assert_eq!(&port.tx[23..26], &[2, 0x00, 0x04]);
+        assert_eq!(port.tx.len(), 27);
+
+        // An acceleration beyond the register range sends nothing at all
+        let mut port = MockPort::new(&[]);
+        let too_fast = [
+            commands[1],
+            ServoPositionCommand {
+                profile: Some(MoveProfile::BySpeed {
+                    speed: 100,
+                    acceleration: 255,
+                }),
+                ..commands[0]
+            },
+        ];
+        assert!(matches!(
+            sync_write_positions(&mut port, &mut buffer, &too_fast),
+            Err(ServoError::ValueOutOfRange(0x29, 255))
+        ));
+        assert!(port.tx.is_empty());
+
+        // Without any profile a single short packet is sent
+        let mut port = MockPort::new(&[]);
+        sync_write_positions(&mut port, &mut buffer, &commands[1..]).unwrap();
+        assert_eq!(&port.tx[4..7], &[0x83, GOAL_POSITION_REGISTER, 2]);
+        assert_eq!(port.tx.len(), 11);
+    }
+
+    #[test]
+    fn test_servo_status_display() {
+        assert_eq!(ServoStatus::empty().to_string(), "none");
+        assert_eq!(
+            (ServoStatus::OVERHEAT | ServoStatus::OVERLOAD).to_string(),
+            "overheat, overload"
+        );
+        assert_eq!(
+            ServoStatus::from_bits_retain(0x41).to_string(),
+            "voltage, unknown 0x40"
+        );
+    }
+
+    #[test]
+    fn test_register_access() {
+        use crate::registers::{PresentPosition, TorqueLimit};
+
+        let mut port = MockPort::new(&status_packet(1, &[0x00, 0x08]));
+        let mut buffer = [0u8; 32];
+        assert_eq!(
+            read_register::<PresentPosition, _>(&mut port, &mut buffer, 1).unwrap(),
+            2048
+        );
+        assert_eq!(&port.tx, &[0xff, 0xff, 1, 4, 0x02, 0x38, 2, 0xbe]);
+
+        let mut port = MockPort::new(&status_packet(1, &[]));
+        write_register::<TorqueLimit, _>(&mut port, &mut buffer, 1, 500).unwrap();
+        assert_eq!(&port.tx[..8], &[0xff, 0xff, 1, 5, 0x03, 0x30, 0xf4, 0x01]);
+
+        let mut port = MockPort::new(&[]);
+        assert!(matches!(
+            write_register::<TorqueLimit, _>(&mut port, &mut buffer, 1, 1001),
+            Err(ServoError::ValueOutOfRange(0x30, 1001))
+        ));
+        assert!(
+            port.tx.is_empty(),
+            "Nothing is written for an out of range value"
+        );
+    }
+
+    #[test]
+    fn test_mode_commands() {
+        let mut port = MockPort::new(&status_packet(1, &[0x03]));
+        let mut buffer = [0u8; 32];
+        assert_eq!(
+            read_operating_mode(&mut port, &mut buffer, 1).unwrap(),
+            Mode::Step
+        );
+
+        let mut port = MockPort::new(&status_packet(1, &[]));
+        write_pwm(&mut port, &mut buffer, 1, -500).unwrap();
+        assert_eq!(&port.tx[4..8], &[0x03, 0x2c, 0xf4, 0x05]);
+
+        let mut port = MockPort::new(&status_packet(1, &[]));
+        move_steps(&mut port, &mut buffer, 1, -4096).unwrap();
+        assert_eq!(&port.tx[4..8], &[0x03, 0x2a, 0x00, 0x90]);
+
+        let mut port = MockPort::new(&[]);
+        assert!(matches!(
+            write_pwm(&mut port, &mut buffer, 1, 1001),
+            Err(ServoError::ValueOutOfRange(0x2c, 1001))
+        ));
+    }
+
+    #[test]
+    fn test_angle_limits() {
+        let mut port = MockPort::new(&status_packet(1, &[0x00, 0x04, 0x00, 0x0c]));
+        let mut buffer = [0u8; 32];
+        assert_eq!(
+            read_angle_limits(&mut port, &mut buffer, 1).unwrap(),
+            1024..=3072
+        );
+        assert_eq!(&port.tx[4..7], &[0x02, 0x09, 4]);
+
+        let mut port = MockPort::new(&[]);
+        assert!(matches!(
+            write_angle_limits(&mut port, &mut buffer, 1, 2048..=2048),
+            Err(ServoError::ValueOutOfRange(0x09, 2048))
+        ));
+    }
+
+    #[test]
+    fn test_position_offset() {
+        // Unlock, offset write and lock
+        let mut port = MockPort::new(&status_packet(1, &[]))
+            .reply(&status_packet(1, &[]))
+            .reply(&status_packet(1, &[]));
+        let mut buffer = [0u8; 32];
+        write_position_offset(&mut port, &mut buffer, 1, -100).unwrap();
+        assert_eq!(&port.tx[12..16], &[0x03, 0x1f, 0x64, 0x08]);
+
+        let mut port = MockPort::new(&status_packet(1, &[0x64, 0x08]));
+        assert_eq!(
+            read_position_offset(&mut port, &mut buffer, 1).unwrap(),
+            -100
+        );
+
+        let mut port = MockPort::new(&[]);
+        assert!(matches!(
+            write_position_offset(&mut port, &mut buffer, 1, 2048),
+            Err(ServoError::ValueOutOfRange(0x1f, 2048))
+        ));
+
+        let mut port = MockPort::new(&status_packet(1, &[]));
+        set_middle_position(&mut port, &mut buffer, 1).unwrap();
+        assert_eq!(&port.tx[4..7], &[0x03, 0x28, 128]);
+    }
+
+    #[test]
+    fn test_set_servo_id() {
+        // Nothing answers on id 7, then unlock, id write, lock and the verifying ping succeed
+        let mut port = MockPort::new(&[])
+            .silence()
+            .reply(&status_packet(3, &[]))
+            .reply(&status_packet(7, &[]))
+            .reply(&status_packet(7, &[]))
+            .reply(&status_packet(7, &[]));
+        let mut buffer = [0u8; 32];
+
+        set_servo_id(&mut port, &mut buffer, 3, 7).unwrap();
+        assert!(port.rx.is_empty());
+
+        let mut port = MockPort::new(&status_packet(7, &[]));
+        assert!(matches!(
+            set_servo_id(&mut port, &mut buffer, 3, 7),
+            Err(ServoError::IdInUse(7))
+        ));
+
+        let mut port = MockPort::new(&[]);
+        assert!(matches!(
+            set_servo_id(&mut port, &mut buffer, BROADCAST_ID, 7),
+            Err(ServoError::ValueOutOfRange(0x05, 0xfe))
+        ));
+        assert!(port.tx.is_empty(), "Nothing is sent for a broadcast id");
+    }
+
+    #[test]
+    fn test_detect_baud_rate() {
+        // The servo only answers once the port runs at 115200 baud
+        let mut port = MockPort::new(&[])
+            .silence()
+            .silence()
+            .silence()
+            .silence()
+            .reply(&status_packet(1, &[]));
+        let mut buffer = [0u8; 32];
+        let mut tried = std::vec::Vec::new();
+
+        let detected = detect_baud_rate(&mut port, &mut buffer, 1, |_, baud_rate| {
+            tried.push(baud_rate);
+            Ok(())
+        });
+
+        assert_eq!(detected.unwrap(), BaudRate::B115200);
+        assert_eq!(tried.len(), 5);
+        assert_eq!(
+            BaudRate::from_code(BaudRate::B115200.code()),
+            Some(BaudRate::B115200)
+        );
+    }
+
+    #[test]
+    fn test_scan_bus() {
+        // Only servo 2 answers: ping, model number, firmware major and minor version
+        let mut port = MockPort::new(&[])
+            .silence()
+            .reply(&status_packet(2, &[]))
+            .reply(&status_packet(2, &[0x09, 0x03]))
+            .reply(&status_packet(2, &[3]))
+            .reply(&status_packet(2, &[10]))
+            .silence();
+        let mut buffer = [0u8; 32];
+
+        let found = scan_bus::<_, 4>(&mut port, &mut buffer, 1..=3).unwrap();
+
+        assert_eq!(
+            found.as_slice(),
+            &[DiscoveredServo {
+                id: 2,
+                model_number: 0x0309,
+                firmware_major: 3,
+                firmware_minor: 10
+            }]
+        );
+    }
+
+    #[test]
+    fn test_reset_servo_reports_new_id() {
+        // Nothing answers on id 1, then the reset reply and the ping after the reboot
+        let mut port = MockPort::new(&[])
+            .silence()
+            .reply(&status_packet(5, &[]))
+            .reply(&status_packet(FACTORY_DEFAULT_ID, &[]));
+        let mut buffer = [0u8; 64];
+        let mut delays = std::vec::Vec::new();
+
+        let new_id = reset_servo(&mut port, &mut buffer, 5, |delay| delays.push(delay)).unwrap();
+
+        assert_eq!(new_id, FACTORY_DEFAULT_ID);
+        assert_eq!(&port.tx[6..12], &[0xff, 0xff, 5, 2, 0x06, 0xf2]);
+        assert_eq!(delays, [RESET_REBOOT_TIME]);
+
+        // Another servo already has id 1: nothing is reset
+        let mut port = MockPort::new(&status_packet(FACTORY_DEFAULT_ID, &[]));
+        assert!(matches!(
+            reset_servo(&mut port, &mut buffer, 5, |_| {}),
+            Err(ServoError::IdInUse(FACTORY_DEFAULT_ID))
+        ));
+        assert_eq!(port.tx.len(), 6, "Only the ping of id 1 is sent");
+    }
 }
@@ -0,0 +1,95 @@
+use std::vec::Vec;
+
+use embedded_io::{ErrorKind, ErrorType, Read, Write};
+
+/// Serial port stand-in that replays canned bytes and records everything written. Once the
+/// canned bytes run out, reads time out like a serial port without data.
+pub(crate) struct MockPort {
+    pub(crate) rx: Vec<u8>,
+    pub(crate) tx: Vec<u8>,
+    chunk_size: usize,
+    /// Offsets into `rx` at which one read times out, for servos that do not answer.
+    silences: Vec<usize>,
+    consumed: usize,
+}
+
+impl MockPort {
+    pub(crate) fn new(rx: &[u8]) -> Self {
+        Self {
+            rx: rx.to_vec(),
+            tx: Vec::new(),
+            chunk_size: usize::MAX,
+            silences: Vec::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Appends bytes to be read.
+    pub(crate) fn reply(mut self, bytes: &[u8]) -> Self {
+        self.rx.extend_from_slice(bytes);
+        self
+    }
+
+    /// Makes the read after the bytes so far time out once.
+    pub(crate) fn silence(mut self) -> Self {
+        self.silences.push(self.consumed + self.rx.len());
+        self
+    }
+
+    /// Limits every read to `chunk_size` bytes, like a USB adapter splitting a packet.
+    pub(crate) fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+}
+
+impl ErrorType for MockPort {
+    type Error = ErrorKind;
+}
+
+impl Read for MockPort {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.silences.first() == Some(&self.consumed) {
+            self.silences.remove(0);
+            return Err(ErrorKind::TimedOut);
+        }
+        if self.rx.is_empty() {
+            return Err(ErrorKind::TimedOut);
+        }
+        let until_silence = self
+            .silences
+            .first()
+            .map_or(usize::MAX, |silence| silence - self.consumed);
+        let count = buf
+            .len()
+            .min(self.rx.len())
+            .min(self.chunk_size)
+            .min(until_silence);
+        buf[..count].copy_from_slice(&self.rx[..count]);
+        self.rx.drain(..count);
+        self.consumed += count;
+        Ok(count)
+    }
+}
+
+impl Write for MockPort {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Builds a status packet without errors carrying `data`.
+pub(crate) fn status_packet(servo_id: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = std::vec![0xff, 0xff, servo_id, data.len() as u8 + 2, 0x00];
+    packet.extend_from_slice(data);
+    let sum = packet[2..]
+        .iter()
+        .fold(0u8, |acc, value| acc.wrapping_add(*value));
+    packet.push(!sum);
+    packet
+}
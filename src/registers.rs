@@ -0,0 +1,260 @@
+//! Typed control table of the STS3215.
+//!
+//! Every register is a zero sized type implementing [`Register`], which fixes its address, width
+//! and valid range. Registers that accept writes also implement [`Writable`], so
+//! [`crate::write_register`] refuses read-only registers at compile time.
+//!
+//! EEPROM registers keep their value across power cycles only while the [`Lock`] register is 0.
+
+/// Where a register lives and whether it can be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Reported by the servo, writes are rejected.
+    ReadOnly,
+    /// RAM, writable at any time and lost on power off.
+    ReadWrite,
+    /// Configuration, persisted when written while the EEPROM is unlocked.
+    Eeprom,
+}
+
+/// Value type of a register: `u8` for one byte registers, `u16` for little endian word registers.
+pub trait RegisterValue: Copy + PartialOrd + Into<u16> {
+    /// Number of bytes the register occupies.
+    const WIDTH: u8;
+
+    /// Converts the raw register contents, which are at most `WIDTH` bytes wide.
+    fn from_raw(raw: u16) -> Self;
+}
+
+impl RegisterValue for u8 {
+    const WIDTH: u8 = 1;
+
+    fn from_raw(raw: u16) -> Self {
+        raw as u8
+    }
+}
+
+impl RegisterValue for u16 {
+    const WIDTH: u8 = 2;
+
+    fn from_raw(raw: u16) -> Self {
+        raw
+    }
+}
+
+pub trait Register {
+    const ADDRESS: u8;
+    const ACCESS: Access;
+    type Value: RegisterValue;
+    /// Smallest raw value the servo accepts.
+    const MIN: Self::Value;
+    /// Largest raw value the servo accepts.
+    const MAX: Self::Value;
+}
+
+/// Marks registers that accept writes.
+pub trait Writable: Register {}
+
+/// Direction bit of [`PresentSpeed`] and of [`GoalSpeed`] in wheel mode.
+pub const SPEED_SIGN_BIT: u8 = 15;
+/// Direction bit of [`PresentLoad`].
+pub const LOAD_SIGN_BIT: u8 = 10;
+/// Direction bit of [`GoalTime`] in PWM mode.
+pub const PWM_SIGN_BIT: u8 = 10;
+/// Direction bit of [`GoalPosition`] in step mode.
+pub const STEP_SIGN_BIT: u8 = 15;
+/// Sign bit of [`PositionOffset`].
+pub const OFFSET_SIGN_BIT: u8 = 11;
+
+/// Decodes a sign-magnitude register value: the bits below `sign_bit` hold the magnitude and a
+/// set `sign_bit` makes it negative.
+pub fn decode_sign_magnitude(raw: u16, sign_bit: u8) -> i16 {
+    let magnitude = (raw & ((1 << sign_bit) - 1)) as i16;
+    if raw & (1 << sign_bit) != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Encodes a value as sign-magnitude, saturating the magnitude to the bits below `sign_bit`.
+pub fn encode_sign_magnitude(value: i16, sign_bit: u8) -> u16 {
+    let magnitude = value.unsigned_abs().min((1 << sign_bit) - 1);
+    if value < 0 {
+        magnitude | (1 << sign_bit)
+    } else {
+        magnitude
+    }
+}
+
+macro_rules! register {
+    ($(#[$doc:meta])* $name:ident, $address:expr, $value:ty, ReadOnly) => {
+        register!(@define $(#[$doc])* $name, $address, $value, ReadOnly, <$value>::MIN, <$value>::MAX);
+    };
+    ($(#[$doc:meta])* $name:ident, $address:expr, $value:ty, $access:ident, $min:expr, $max:expr) => {
+        register!(@define $(#[$doc])* $name, $address, $value, $access, $min, $max);
+        impl Writable for $name {}
+    };
+    (@define $(#[$doc:meta])* $name:ident, $address:expr, $value:ty, $access:ident, $min:expr, $max:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name;
+
+        impl Register for $name {
+            const ADDRESS: u8 = $address;
+            const ACCESS: Access = Access::$access;
+            type Value = $value;
+            const MIN: $value = $min;
+            const MAX: $value = $max;
+        }
+    };
+}
+
+// EEPROM
+
+register!(FirmwareMajorVersion, 0x00, u8, ReadOnly);
+register!(FirmwareMinorVersion, 0x01, u8, ReadOnly);
+register!(ModelNumber, 0x03, u16, ReadOnly);
+register!(
+    /// Bus address of the servo. 254 is the broadcast id.
+    Id, 0x05, u8, Eeprom, 0, 253
+);
+register!(
+    /// Baud rate code, 0 is 1,000,000 baud.
+    BaudRate, 0x06, u8, Eeprom, 0, 7
+);
+register!(
+    /// Delay before the servo replies, in units of 2 µs.
+    ReturnDelayTime, 0x07, u8, Eeprom, 0, 254
+);
+register!(
+    /// 0 only answers READ and PING, 1 answers every instruction.
+    ResponseStatusLevel, 0x08, u8, Eeprom, 0, 1
+);
+register!(MinAngleLimit, 0x09, u16, Eeprom, 0, 4094);
+register!(MaxAngleLimit, 0x0B, u16, Eeprom, 1, 4095);
+register!(
+    /// Temperature in °C above which the servo protects itself.
+    MaxTemperatureLimit, 0x0D, u8, Eeprom, 0, 100
+);
+register!(
+    /// In units of 0.1 V.
+    MaxVoltageLimit, 0x0E, u8, Eeprom, 0, 254
+);
+register!(
+    /// In units of 0.1 V.
+    MinVoltageLimit, 0x0F, u8, Eeprom, 0, 254
+);
+register!(
+    /// Torque limit loaded at power on, in 0.1% of the maximum torque.
+    MaxTorqueLimit, 0x10, u16, Eeprom, 0, 1000
+);
+register!(Phase, 0x12, u8, Eeprom, 0, 254);
+register!(
+    /// Which [`crate::ServoStatus`] faults release the torque.
+    UnloadingCondition, 0x13, u8, Eeprom, 0, 254
+);
+register!(
+    /// Which [`crate::ServoStatus`] faults flash the LED.
+    LedAlarmCondition, 0x14, u8, Eeprom, 0, 254
+);
+register!(PCoefficient, 0x15, u8, Eeprom, 0, 254);
+register!(DCoefficient, 0x16, u8, Eeprom, 0, 254);
+register!(ICoefficient, 0x17, u8, Eeprom, 0, 254);
+register!(
+    /// In 0.1% of the maximum torque.
+    MinimumStartupForce, 0x18, u16, Eeprom, 0, 1000
+);
+register!(CwDeadZone, 0x1A, u8, Eeprom, 0, 32);
+register!(CcwDeadZone, 0x1B, u8, Eeprom, 0, 32);
+register!(
+    /// In units of 6.5 mA.
+    ProtectionCurrent, 0x1C, u16, Eeprom, 0, 511
+);
+register!(AngularResolution, 0x1E, u8, Eeprom, 1, 100);
+register!(
+    /// Position correction, sign-magnitude with the sign in bit 11.
+    PositionOffset, 0x1F, u16, Eeprom, 0, 4095
+);
+register!(
+    /// 0 position, 1 wheel, 2 PWM, 3 step.
+    OperatingMode, 0x21, u8, Eeprom, 0, 3
+);
+register!(
+    /// Torque after an overload protection triggers, in % of the maximum torque.
+    ProtectiveTorque, 0x22, u8, Eeprom, 0, 100
+);
+register!(
+    /// How long the load may exceed the overload torque, in units of 10 ms.
+    ProtectionTime, 0x23, u8, Eeprom, 0, 254
+);
+register!(
+    /// Load that starts the protection timer, in % of the maximum torque.
+    OverloadTorque, 0x24, u8, Eeprom, 0, 100
+);
+register!(SpeedPCoefficient, 0x25, u8, Eeprom, 0, 100);
+register!(
+    /// In units of 10 ms.
+    OverCurrentProtectionTime, 0x26, u8, Eeprom, 0, 254
+);
+register!(SpeedICoefficient, 0x27, u8, Eeprom, 0, 254);
+
+// SRAM
+
+register!(
+    /// 0 releases the torque, 1 enables it and 128 makes the current position the mid-point.
+    TorqueEnable, 0x28, u8, ReadWrite, 0, 128
+);
+register!(
+    /// In units of 100 steps/s², 0 is the maximum acceleration.
+    Acceleration, 0x29, u8, ReadWrite, 0, 254
+);
+register!(
+    /// Absolute position, or in step mode the steps to move: sign-magnitude with the sign in bit 15.
+    GoalPosition, 0x2A, u16, ReadWrite, 0, 4095
+);
+register!(
+    /// Time to reach the goal position in ms, or the PWM output in PWM mode: sign-magnitude with
+    /// the sign in bit 10, in 0.1% of the maximum output.
+    GoalTime, 0x2C, u16, ReadWrite, 0, u16::MAX
+);
+register!(
+    /// In steps/s, sign-magnitude with the sign in bit 15 in wheel mode.
+    GoalSpeed, 0x2E, u16, ReadWrite, 0, u16::MAX
+);
+register!(
+    /// In 0.1% of the maximum torque.
+    TorqueLimit, 0x30, u16, ReadWrite, 0, 1000
+);
+register!(
+    /// 0 unlocks the EEPROM so writes to it persist, 1 locks it.
+    Lock, 0x37, u8, ReadWrite, 0, 1
+);
+register!(PresentPosition, 0x38, u16, ReadOnly);
+register!(PresentSpeed, 0x3A, u16, ReadOnly);
+register!(PresentLoad, 0x3C, u16, ReadOnly);
+register!(PresentVoltage, 0x3E, u8, ReadOnly);
+register!(PresentTemperature, 0x3F, u8, ReadOnly);
+register!(AsyncWriteFlag, 0x40, u8, ReadOnly);
+register!(Status, 0x41, u8, ReadOnly);
+register!(Moving, 0x42, u8, ReadOnly);
+register!(PresentCurrent, 0x45, u16, ReadOnly);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_magnitude() {
+        // A slow reverse movement reads as 32800 when taken as unsigned
+        assert_eq!(decode_sign_magnitude(32800, SPEED_SIGN_BIT), -32);
+        assert_eq!(decode_sign_magnitude(32, SPEED_SIGN_BIT), 32);
+        assert_eq!(decode_sign_magnitude(0x0400 | 250, LOAD_SIGN_BIT), -250);
+        assert_eq!(decode_sign_magnitude(0x0800 | 100, OFFSET_SIGN_BIT), -100);
+
+        assert_eq!(encode_sign_magnitude(-32, SPEED_SIGN_BIT), 32800);
+        assert_eq!(encode_sign_magnitude(1000, SPEED_SIGN_BIT), 1000);
+        assert_eq!(encode_sign_magnitude(i16::MIN, SPEED_SIGN_BIT), 0xFFFF);
+        assert_eq!(encode_sign_magnitude(-3000, OFFSET_SIGN_BIT), 0x0FFF);
+    }
+}
@@ -0,0 +1,621 @@
+//! Interpolation between timestamped joint waypoints, for streaming smooth motion to the servos
+//! at a fixed control rate. Needs neither std nor an allocator.
+//!
+//! ```ignore
+//! let waypoints = [
+//!     Waypoint::new(Duration::ZERO, [2048, 2048]),
+//!     Waypoint::new(Duration::from_millis(100), [2100, 2000]),
+//!     Waypoint::new(Duration::from_millis(200), [2200, 1900]),
+//! ];
+//! let trajectory = Trajectory::new(&waypoints, Profile::CubicSpline)?;
+//! robot.execute_trajectory(&trajectory, 50, std::thread::sleep)?;
+//! ```
+
+use core::time::Duration;
+
+use crate::{
+    ServoError,
+    units::{MAX_POSITION, round, sqrt},
+};
+
+/// Relative slack for rounding errors when checking that blends fit their segments.
+const TOLERANCE: f32 = 1e-4;
+
+/// Steps of the bisection for speeds whose blends never reach the acceleration limit.
+const BISECTION_STEPS: usize = 32;
+
+/// Goal positions of all joints at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Waypoint<const N: usize> {
+    /// Time since the start of the trajectory.
+    pub time: Duration,
+    pub positions: [u16; N],
+}
+
+impl<const N: usize> Waypoint<N> {
+    pub fn new(time: Duration, positions: [u16; N]) -> Self {
+        Self { time, positions }
+    }
+}
+
+/// How positions move between waypoints.
+///
+/// The blended profiles cruise at constant speed along each segment and change speed in blends
+/// centred on the interior waypoints, so they pass close to but not exactly through them. They
+/// start and end at rest exactly on the first and last waypoint. Limits are in steps/s, steps/s²
+/// and steps/s³.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Profile {
+    /// Constant speed within each segment; the speed jumps at every waypoint.
+    #[default]
+    Linear,
+    /// Catmull-Rom spline through all waypoints: the velocity is continuous and the joints only
+    /// stop at the first and last waypoint. May overshoot around sharp turns.
+    CubicSpline,
+    /// Blends at constant acceleration, giving a trapezoidal velocity profile.
+    Trapezoidal {
+        max_velocity: f32,
+        max_acceleration: f32,
+    },
+    /// Blends whose acceleration ramps at a limited jerk, giving an S-shaped velocity profile.
+    SCurve {
+        max_velocity: f32,
+        max_acceleration: f32,
+        max_jerk: f32,
+    },
+}
+
+impl Profile {
+    /// Limits of the blended profiles, with unlimited jerk for the trapezoidal one.
+    fn limits(self) -> Option<Limits> {
+        match self {
+            Profile::Linear | Profile::CubicSpline => None,
+            Profile::Trapezoidal {
+                max_velocity,
+                max_acceleration,
+            } => Some(Limits {
+                velocity: max_velocity,
+                acceleration: max_acceleration,
+                jerk: f32::INFINITY,
+            }),
+            Profile::SCurve {
+                max_velocity,
+                max_acceleration,
+                max_jerk,
+            } => Some(Limits {
+                velocity: max_velocity,
+                acceleration: max_acceleration,
+                jerk: max_jerk,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    velocity: f32,
+    acceleration: f32,
+    jerk: f32,
+}
+
+impl Limits {
+    /// Speed covering `distance` steps in `span` seconds of a segment that starts or ends at
+    /// rest, where `rest` is the share of the rest blends spent inside the segment. `None` if the
+    /// acceleration is too low to get there in time.
+    fn cruise_speed(&self, distance: f32, span: f32, rest: f32) -> Option<f32> {
+        if distance == 0.0 {
+            return Some(0.0);
+        }
+        let (acceleration, jerk) = (self.acceleration, self.jerk);
+        // Speeds up to `full` blend without reaching the acceleration limit; the distance they
+        // cover grows until `limit`
+        let full = acceleration * acceleration / jerk;
+        let covered = |speed: f32| speed * (span - 2.0 * rest * sqrt(speed / jerk));
+        let third = span / (3.0 * rest);
+        let limit = full.min(jerk * third * third);
+        if covered(limit) >= distance {
+            let (mut low, mut high) = (0.0, limit);
+            for _ in 0..BISECTION_STEPS {
+                let middle = (low + high) / 2.0;
+                if covered(middle) < distance {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            return Some(high);
+        }
+        // Otherwise the blend lasts speed / acceleration + acceleration / jerk, a quadratic
+        let span = span - rest * acceleration / jerk;
+        let discriminant = span * span - 4.0 * rest * distance / acceleration;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let speed = (span - sqrt(discriminant)) * acceleration / (2.0 * rest);
+        (speed >= full * (1.0 - TOLERANCE)).then_some(speed)
+    }
+}
+
+/// Velocity change around a waypoint: the acceleration ramps up at the jerk limit, holds its peak
+/// and ramps back down.
+struct Blend {
+    change: f32,
+    peak: f32,
+    jerk: f32,
+    ramp: f32,
+    duration: f32,
+}
+
+impl Blend {
+    fn new(limits: &Limits, change: f32) -> Self {
+        let (magnitude, jerk) = (change.abs(), limits.jerk);
+        let peak = if magnitude * jerk >= limits.acceleration * limits.acceleration {
+            limits.acceleration
+        } else {
+            sqrt(magnitude * jerk)
+        };
+        let (ramp, duration) = if magnitude == 0.0 {
+            (0.0, 0.0)
+        } else {
+            (peak / jerk, magnitude / peak + peak / jerk)
+        };
+        Self {
+            change,
+            peak,
+            jerk,
+            ramp,
+            duration,
+        }
+    }
+
+    /// Distance gained over keeping the incoming velocity, `time` seconds into the blend.
+    fn offset(&self, time: f32) -> f32 {
+        let time = time.clamp(0.0, self.duration);
+        let offset = if self.ramp > 0.0 {
+            let cube = |time: f32| time.max(0.0) * time.max(0.0) * time.max(0.0);
+            self.jerk / 6.0
+                * (cube(time) - cube(time - self.ramp) - cube(time - self.duration + self.ramp)
+                    + cube(time - self.duration))
+        } else {
+            self.peak * time * time / 2.0
+        };
+        offset.copysign(self.change)
+    }
+}
+
+/// A path through waypoints that can be sampled at any time.
+#[derive(Debug, Clone, Copy)]
+pub struct Trajectory<'a, const N: usize> {
+    waypoints: &'a [Waypoint<N>],
+    profile: Profile,
+}
+
+impl<'a, const N: usize> Trajectory<'a, N> {
+    /// Fails with [`ServoError::InvalidTrajectory`] unless there is at least one waypoint, the
+    /// waypoint times strictly increase and the profile limits are positive, with a finite
+    /// velocity and acceleration. Fails with [`ServoError::TrajectoryLimitExceeded`] if a joint
+    /// cannot keep to the waypoint times within the limits.
+    pub fn new(waypoints: &'a [Waypoint<N>], profile: Profile) -> Result<Self, ServoError> {
+        if waypoints.is_empty()
+            || waypoints
+                .windows(2)
+                .any(|pair| pair[0].time >= pair[1].time)
+        {
+            return Err(ServoError::InvalidTrajectory);
+        }
+        let trajectory = Self { waypoints, profile };
+        if let Some(limits) = profile.limits() {
+            let finite = |limit: f32| limit > 0.0 && limit.is_finite();
+            if !(finite(limits.velocity) && finite(limits.acceleration) && limits.jerk > 0.0) {
+                return Err(ServoError::InvalidTrajectory);
+            }
+            for joint in 0..N {
+                if !(0..waypoints.len() - 1).all(|segment| trajectory.fits(&limits, segment, joint))
+                {
+                    return Err(ServoError::TrajectoryLimitExceeded(joint));
+                }
+            }
+        }
+        Ok(trajectory)
+    }
+
+    /// Time of the first waypoint.
+    pub fn start(&self) -> Duration {
+        self.waypoints[0].time
+    }
+
+    /// Time of the last waypoint.
+    pub fn end(&self) -> Duration {
+        self.waypoints[self.waypoints.len() - 1].time
+    }
+
+    /// Positions at `time`, holding the first and last waypoint outside the trajectory.
+    pub fn sample(&self, time: Duration) -> [u16; N] {
+        let last = self.waypoints.len() - 1;
+        if time <= self.start() {
+            return self.waypoints[0].positions;
+        }
+        if time >= self.end() {
+            return self.waypoints[last].positions;
+        }
+        core::array::from_fn(|joint| {
+            round(self.position(time, joint)).clamp(0, MAX_POSITION as i32) as u16
+        })
+    }
+
+    /// Setpoints every `1 / rate_hz` seconds from the first to the last waypoint, which is always
+    /// included.
+    pub fn setpoints(&self, rate_hz: u32) -> Setpoints<'_, 'a, N> {
+        Setpoints {
+            trajectory: self,
+            period: period(rate_hz),
+            next: Some(self.start()),
+        }
+    }
+
+    /// Unrounded position of a joint at a `time` between the first and last waypoint.
+    fn position(&self, time: Duration, joint: usize) -> f32 {
+        let segment = self
+            .waypoints
+            .windows(2)
+            .position(|pair| time < pair[1].time)
+            .unwrap_or(self.waypoints.len() - 2);
+        let (from, to) = (&self.waypoints[segment], &self.waypoints[segment + 1]);
+        let span = (to.time - from.time).as_secs_f32();
+        let elapsed = (time - from.time).as_secs_f32();
+        let t = elapsed / span;
+        let start = from.positions[joint] as f32;
+        let end = to.positions[joint] as f32;
+        match (self.profile, self.profile.limits()) {
+            (_, Some(limits)) => self.blended(&limits, segment, joint, elapsed),
+            (Profile::CubicSpline, _) => {
+                let start_slope = self.velocity(segment, joint) * span;
+                let end_slope = self.velocity(segment + 1, joint) * span;
+                hermite(start, start_slope, end, end_slope, t)
+            }
+            _ => start + (end - start) * t,
+        }
+    }
+
+    /// Position of a joint `elapsed` seconds into a segment of a blended profile: the line at the
+    /// segment's cruise velocity, bent into the blends around its waypoints.
+    fn blended(&self, limits: &Limits, segment: usize, joint: usize, elapsed: f32) -> f32 {
+        // Trajectory::new checked that every segment keeps to the limits
+        let velocity = |segment| self.cruise_velocity(limits, segment, joint).unwrap_or(0.0);
+        let blend = |waypoint| {
+            let change = self.velocity_change(limits, waypoint, joint).unwrap_or(0.0);
+            Blend::new(limits, change)
+        };
+        let start = self.waypoints[segment].positions[joint] as f32;
+        let span = (self.waypoints[segment + 1].time - self.waypoints[segment].time).as_secs_f32();
+        let (entry, exit) = (blend(segment), blend(segment + 1));
+        let entry_start = self.blend_start(segment, entry.duration);
+        let exit_start = span + self.blend_start(segment + 1, exit.duration);
+
+        if elapsed < entry_start + entry.duration {
+            // Still bending away from the previous line, which passes through this waypoint
+            let incoming = if segment == 0 {
+                0.0
+            } else {
+                velocity(segment - 1)
+            };
+            return start + incoming * elapsed + entry.offset(elapsed - entry_start);
+        }
+        // The first line is shifted to leave time for starting from rest
+        let delay = if segment == 0 {
+            entry.duration / 2.0
+        } else {
+            0.0
+        };
+        let line = start + velocity(segment) * (elapsed - delay);
+        if elapsed >= exit_start {
+            line + exit.offset(elapsed - exit_start)
+        } else {
+            line
+        }
+    }
+
+    /// Whether a joint keeps to the limits on a segment: its cruise velocity is reachable and
+    /// within the velocity limit, and the blends at both ends fit inside the segment.
+    fn fits(&self, limits: &Limits, segment: usize, joint: usize) -> bool {
+        let duration = |waypoint| {
+            let change = self.velocity_change(limits, waypoint, joint)?;
+            Some(Blend::new(limits, change).duration)
+        };
+        let (Some(entry), Some(exit)) = (duration(segment), duration(segment + 1)) else {
+            return false;
+        };
+        let span = (self.waypoints[segment + 1].time - self.waypoints[segment].time).as_secs_f32();
+        let used = self.blend_start(segment, entry) + entry - self.blend_start(segment + 1, exit);
+        used <= span * (1.0 + TOLERANCE)
+    }
+
+    /// Start of the blend around a waypoint relative to the waypoint time: blends are centred on
+    /// interior waypoints, start at the first and end at the last.
+    fn blend_start(&self, waypoint: usize, duration: f32) -> f32 {
+        if waypoint == 0 {
+            0.0
+        } else if waypoint == self.waypoints.len() - 1 {
+            -duration
+        } else {
+            -duration / 2.0
+        }
+    }
+
+    /// Change from the incoming to the outgoing cruise velocity of a joint at a waypoint, where the
+    /// joint rests before the first and after the last.
+    fn velocity_change(&self, limits: &Limits, waypoint: usize, joint: usize) -> Option<f32> {
+        let incoming = match waypoint {
+            0 => 0.0,
+            _ => self.cruise_velocity(limits, waypoint - 1, joint)?,
+        };
+        let outgoing = if waypoint + 1 < self.waypoints.len() {
+            self.cruise_velocity(limits, waypoint, joint)?
+        } else {
+            0.0
+        };
+        Some(outgoing - incoming)
+    }
+
+    /// Constant velocity of a joint along a segment in steps/s, or `None` if it breaks the limits.
+    /// Segments between interior waypoints run straight from one to the other; segments at the
+    /// ends run faster to make up for the time spent starting or stopping.
+    fn cruise_velocity(&self, limits: &Limits, segment: usize, joint: usize) -> Option<f32> {
+        let (from, to) = (&self.waypoints[segment], &self.waypoints[segment + 1]);
+        let distance = to.positions[joint] as f32 - from.positions[joint] as f32;
+        let span = (to.time - from.time).as_secs_f32();
+        let last = self.waypoints.len() - 2;
+        let rest = match (segment == 0, segment == last) {
+            (false, false) => return Some(distance / span).filter(|v| v.abs() <= limits.velocity),
+            (true, true) => 1.0,
+            _ => 0.5,
+        };
+        let speed = limits.cruise_speed(distance.abs(), span, rest)?;
+        (speed <= limits.velocity).then_some(speed.copysign(distance))
+    }
+
+    /// Spline velocity of a joint at a waypoint in steps/s: the slope between its neighbours, and
+    /// zero at both ends.
+    fn velocity(&self, index: usize, joint: usize) -> f32 {
+        if index == 0 || index == self.waypoints.len() - 1 {
+            return 0.0;
+        }
+        let (before, after) = (&self.waypoints[index - 1], &self.waypoints[index + 1]);
+        let distance = after.positions[joint] as f32 - before.positions[joint] as f32;
+        distance / (after.time - before.time).as_secs_f32()
+    }
+}
+
+/// Iterator over the time and positions of each setpoint, see [`Trajectory::setpoints`].
+pub struct Setpoints<'t, 'a, const N: usize> {
+    trajectory: &'t Trajectory<'a, N>,
+    period: Duration,
+    next: Option<Duration>,
+}
+
+impl<const N: usize> Iterator for Setpoints<'_, '_, N> {
+    type Item = (Duration, [u16; N]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let time = self.next?;
+        let end = self.trajectory.end();
+        self.next = (time < end).then(|| (time + self.period).min(end));
+        Some((time, self.trajectory.sample(time)))
+    }
+}
+
+/// Time between setpoints at `rate_hz`, treating 0 as 1 Hz. At least 1 ns, so setpoints always
+/// advance even above 1 GHz.
+pub(crate) fn period(rate_hz: u32) -> Duration {
+    (Duration::from_secs(1) / rate_hz.max(1)).max(Duration::from_nanos(1))
+}
+
+/// Cubic Hermite interpolation, with slopes in position per segment.
+fn hermite(start: f32, start_slope: f32, end: f32, end_slope: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * start
+        + (t3 - 2.0 * t2 + t) * start_slope
+        + (-2.0 * t3 + 3.0 * t2) * end
+        + (t3 - t2) * end_slope
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waypoints() -> [Waypoint<2>; 3] {
+        [
+            Waypoint::new(Duration::ZERO, [1000, 3000]),
+            Waypoint::new(Duration::from_millis(300), [1300, 3000]),
+            Waypoint::new(Duration::from_millis(600), [1000, 2400]),
+        ]
+    }
+
+    fn sample(profile: Profile, millis: u64) -> [u16; 2] {
+        let waypoints = waypoints();
+        Trajectory::new(&waypoints, profile)
+            .unwrap()
+            .sample(Duration::from_millis(millis))
+    }
+
+    const TRAPEZOIDAL: Profile = Profile::Trapezoidal {
+        max_velocity: 3000.0,
+        max_acceleration: 20000.0,
+    };
+
+    const S_CURVE: Profile = Profile::SCurve {
+        max_velocity: 3000.0,
+        max_acceleration: 20000.0,
+        max_jerk: 400000.0,
+    };
+
+    #[test]
+    fn test_profiles_pass_through_waypoints() {
+        for profile in [Profile::Linear, Profile::CubicSpline] {
+            assert_eq!(sample(profile, 0), [1000, 3000]);
+            assert_eq!(sample(profile, 300), [1300, 3000]);
+            assert_eq!(sample(profile, 600), [1000, 2400]);
+            assert_eq!(
+                sample(profile, 900),
+                [1000, 2400],
+                "Holds the last waypoint"
+            );
+        }
+        assert_eq!(sample(Profile::Linear, 150), [1150, 3000]);
+        // Blended profiles start and end on the waypoints and cut the corner in between
+        for profile in [TRAPEZOIDAL, S_CURVE] {
+            assert_eq!(sample(profile, 0), [1000, 3000]);
+            assert_eq!(sample(profile, 600), [1000, 2400]);
+            assert!(sample(profile, 300)[0] < 1300);
+        }
+    }
+
+    #[test]
+    fn test_profile_shapes() {
+        assert_eq!(sample(Profile::Linear, 50), [1050, 3000]);
+        // The spline keeps the second joint moving through the middle waypoint, away from 3000
+        assert!(sample(Profile::CubicSpline, 200)[1] > 3000);
+        assert!(sample(Profile::CubicSpline, 400)[1] < 3000);
+        // Accelerating at 20000 steps/s² for 10 ms covers one step
+        assert_eq!(sample(TRAPEZOIDAL, 10), [1001, 3000]);
+        // Ramping the acceleration up at the jerk limit covers less
+        assert_eq!(sample(S_CURVE, 10), [1000, 3000]);
+    }
+
+    /// Unrounded positions of a joint every `step` from the first to the last waypoint.
+    fn positions(trajectory: &Trajectory<'_, 1>, step: Duration) -> std::vec::Vec<f32> {
+        let count = trajectory.end().as_nanos() / step.as_nanos();
+        (1..count as u32)
+            .map(|index| trajectory.position(step * index, 0))
+            .collect()
+    }
+
+    /// Differences between consecutive values, divided by `step` in seconds.
+    fn rates(values: &[f32], step: Duration) -> std::vec::Vec<f32> {
+        let step = step.as_secs_f32();
+        values
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]) / step)
+            .collect()
+    }
+
+    #[test]
+    fn test_blended_profiles_keep_to_limits() {
+        // Straight through evenly spaced waypoints: the joint should not stop in between
+        let waypoints = [0, 1, 2, 3].map(|index| {
+            Waypoint::new(
+                Duration::from_millis(500) * index,
+                [1000 + 500 * index as u16],
+            )
+        });
+        let step = Duration::from_millis(1);
+        for (profile, max_jerk) in [
+            (
+                Profile::Trapezoidal {
+                    max_velocity: 2000.0,
+                    max_acceleration: 10000.0,
+                },
+                None,
+            ),
+            (
+                Profile::SCurve {
+                    max_velocity: 2000.0,
+                    max_acceleration: 10000.0,
+                    max_jerk: 200000.0,
+                },
+                Some(200000.0),
+            ),
+        ] {
+            let trajectory = Trajectory::new(&waypoints, profile).unwrap();
+            let velocities = rates(&positions(&trajectory, step), step);
+            assert!(velocities.iter().all(|&velocity| velocity <= 2000.0));
+            assert!(velocities[499] > 900.0 && velocities[999] > 900.0);
+            assert!(velocities[0] < 20.0 && velocities[velocities.len() - 1] < 20.0);
+
+            // Coarser steps keep the rounding noise of higher differences down
+            let step = Duration::from_millis(5);
+            let accelerations = rates(&rates(&positions(&trajectory, step), step), step);
+            assert!(accelerations.iter().all(|&a| a.abs() <= 10000.0 * 1.02));
+            if let Some(max_jerk) = max_jerk {
+                let step = Duration::from_millis(10);
+                let velocities = rates(&positions(&trajectory, step), step);
+                let jerks = rates(&rates(&velocities, step), step);
+                assert!(jerks.iter().all(|&jerk| jerk.abs() <= max_jerk * 1.02));
+            }
+        }
+    }
+
+    #[test]
+    fn test_trajectory_limits() {
+        let waypoints = waypoints();
+        assert!(Trajectory::new(&waypoints, TRAPEZOIDAL).is_ok());
+        // The second joint has to cruise at about 2536 steps/s to make up for stopping
+        assert!(matches!(
+            Trajectory::new(
+                &waypoints,
+                Profile::Trapezoidal {
+                    max_velocity: 2500.0,
+                    max_acceleration: 20000.0,
+                }
+            ),
+            Err(ServoError::TrajectoryLimitExceeded(1))
+        ));
+        // Too little acceleration to cover the first joint's 300 steps in 300 ms from rest
+        assert!(matches!(
+            Trajectory::new(
+                &waypoints,
+                Profile::SCurve {
+                    max_velocity: 3000.0,
+                    max_acceleration: 5000.0,
+                    max_jerk: 400000.0,
+                }
+            ),
+            Err(ServoError::TrajectoryLimitExceeded(0))
+        ));
+        for max_jerk in [0.0, f32::NAN] {
+            let profile = Profile::SCurve {
+                max_velocity: 3000.0,
+                max_acceleration: 20000.0,
+                max_jerk,
+            };
+            assert!(matches!(
+                Trajectory::new(&waypoints, profile),
+                Err(ServoError::InvalidTrajectory)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_setpoints() {
+        let waypoints = waypoints();
+        let trajectory = Trajectory::new(&waypoints, Profile::Linear).unwrap();
+
+        let times: std::vec::Vec<_> = trajectory.setpoints(8).map(|(time, _)| time).collect();
+        assert_eq!(
+            times,
+            [0, 125, 250, 375, 500, 600].map(Duration::from_millis),
+            "The last waypoint ends the setpoints"
+        );
+        assert_eq!(trajectory.setpoints(8).last().unwrap().1, [1000, 2400]);
+
+        // A rate too high for a whole nanosecond still ends
+        let waypoints = [
+            Waypoint::new(Duration::ZERO, [1000]),
+            Waypoint::new(Duration::from_nanos(3), [1003]),
+        ];
+        let trajectory = Trajectory::new(&waypoints, Profile::Linear).unwrap();
+        assert_eq!(trajectory.setpoints(2_000_000_000).count(), 4);
+    }
+
+    #[test]
+    fn test_invalid_trajectory() {
+        let mut waypoints = waypoints();
+        waypoints[2].time = waypoints[1].time;
+        assert!(matches!(
+            Trajectory::new(&waypoints, Profile::Linear),
+            Err(ServoError::InvalidTrajectory)
+        ));
+        assert!(Trajectory::<2>::new(&[], Profile::Linear).is_err());
+    }
+}
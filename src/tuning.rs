@@ -0,0 +1,232 @@
+//! Control loop gains and protection thresholds, kept in EEPROM.
+
+use embedded_io::{Read, Write};
+
+use crate::{
+    ServoError, check_range,
+    comm::Command,
+    registers::{
+        CcwDeadZone, CwDeadZone, DCoefficient, ICoefficient, Lock, MaxTorqueLimit,
+        OverCurrentProtectionTime, OverloadTorque, PCoefficient, ProtectionCurrent, ProtectionTime,
+        ProtectiveTorque, Register, RegisterValue, Writable,
+    },
+    write_register,
+};
+
+/// Registers from [`MaxTorqueLimit`] up to and including [`OverCurrentProtectionTime`], read in
+/// one go.
+const TUNING_BLOCK_LENGTH: u8 = OverCurrentProtectionTime::ADDRESS + 1 - MaxTorqueLimit::ADDRESS;
+
+/// How stiffly a servo holds its position and when it protects itself.
+///
+/// Lower gains and torque make a joint softer, as for a gripper; higher gains make it stiffer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServoTuning {
+    pub p_coefficient: u8,
+    pub i_coefficient: u8,
+    pub d_coefficient: u8,
+    /// Position error ignored clockwise, in steps.
+    pub cw_dead_zone: u8,
+    /// Position error ignored counterclockwise, in steps.
+    pub ccw_dead_zone: u8,
+    /// In 0.1% of the maximum torque.
+    pub max_torque: u16,
+    /// In units of 6.5 mA.
+    pub protection_current: u16,
+    /// In units of 10 ms.
+    pub over_current_protection_time: u8,
+    /// Load that starts the protection timer, in % of the maximum torque.
+    pub overload_torque: u8,
+    /// How long the load may exceed the overload torque, in units of 10 ms.
+    pub protection_time: u8,
+    /// Torque after an overload protection triggers, in % of the maximum torque.
+    pub protective_torque: u8,
+}
+
+impl ServoTuning {
+    /// Reads the tuning of a servo with a single READ.
+    pub fn read<P: Write + Read>(
+        port: &mut P,
+        buffer: &mut [u8],
+        servo_id: u8,
+    ) -> Result<Self, ServoError> {
+        let response = Command::Read(servo_id, MaxTorqueLimit::ADDRESS, TUNING_BLOCK_LENGTH)
+            .send_command(port, buffer)?;
+        let block = response.data();
+        if block.len() < TUNING_BLOCK_LENGTH as usize {
+            return Err(ServoError::ResponseParseError);
+        }
+        Ok(Self {
+            p_coefficient: field::<PCoefficient>(block),
+            i_coefficient: field::<ICoefficient>(block),
+            d_coefficient: field::<DCoefficient>(block),
+            cw_dead_zone: field::<CwDeadZone>(block),
+            ccw_dead_zone: field::<CcwDeadZone>(block),
+            max_torque: field::<MaxTorqueLimit>(block),
+            protection_current: field::<ProtectionCurrent>(block),
+            over_current_protection_time: field::<OverCurrentProtectionTime>(block),
+            overload_torque: field::<OverloadTorque>(block),
+            protection_time: field::<ProtectionTime>(block),
+            protective_torque: field::<ProtectiveTorque>(block),
+        })
+    }
+
+    /// Stores the tuning in the EEPROM of a servo. Every value is range checked before anything
+    /// is written, and the EEPROM is relocked even when a write fails.
+    pub fn write<P: Write + Read>(
+        &self,
+        port: &mut P,
+        buffer: &mut [u8],
+        servo_id: u8,
+    ) -> Result<(), ServoError> {
+        check_range::<PCoefficient>(self.p_coefficient)?;
+        check_range::<ICoefficient>(self.i_coefficient)?;
+        check_range::<DCoefficient>(self.d_coefficient)?;
+        check_range::<CwDeadZone>(self.cw_dead_zone)?;
+        check_range::<CcwDeadZone>(self.ccw_dead_zone)?;
+        check_range::<MaxTorqueLimit>(self.max_torque)?;
+        check_range::<ProtectionCurrent>(self.protection_current)?;
+        check_range::<OverCurrentProtectionTime>(self.over_current_protection_time)?;
+        check_range::<OverloadTorque>(self.overload_torque)?;
+        check_range::<ProtectionTime>(self.protection_time)?;
+        check_range::<ProtectiveTorque>(self.protective_torque)?;
+
+        write_register::<Lock, _>(port, buffer, servo_id, 0)?;
+        let written = self.write_unlocked(port, buffer, servo_id);
+        let locked = write_register::<Lock, _>(port, buffer, servo_id, 1);
+        written.and(locked)
+    }
+
+    /// Reads the tuning back and fails with [`ServoError::VerificationFailed`] unless it matches.
+    pub fn verify<P: Write + Read>(
+        &self,
+        port: &mut P,
+        buffer: &mut [u8],
+        servo_id: u8,
+    ) -> Result<(), ServoError> {
+        if Self::read(port, buffer, servo_id)? != *self {
+            return Err(ServoError::VerificationFailed(servo_id));
+        }
+        Ok(())
+    }
+
+    fn write_unlocked<P: Write + Read>(
+        &self,
+        port: &mut P,
+        buffer: &mut [u8],
+        servo_id: u8,
+    ) -> Result<(), ServoError> {
+        write_register::<PCoefficient, _>(port, buffer, servo_id, self.p_coefficient)?;
+        write_register::<ICoefficient, _>(port, buffer, servo_id, self.i_coefficient)?;
+        write_register::<DCoefficient, _>(port, buffer, servo_id, self.d_coefficient)?;
+        write_register::<CwDeadZone, _>(port, buffer, servo_id, self.cw_dead_zone)?;
+        write_register::<CcwDeadZone, _>(port, buffer, servo_id, self.ccw_dead_zone)?;
+        write_register::<MaxTorqueLimit, _>(port, buffer, servo_id, self.max_torque)?;
+        write_register::<ProtectionCurrent, _>(port, buffer, servo_id, self.protection_current)?;
+        write_register::<OverCurrentProtectionTime, _>(
+            port,
+            buffer,
+            servo_id,
+            self.over_current_protection_time,
+        )?;
+        write_register::<OverloadTorque, _>(port, buffer, servo_id, self.overload_torque)?;
+        write_register::<ProtectionTime, _>(port, buffer, servo_id, self.protection_time)?;
+        write_register::<ProtectiveTorque, _>(port, buffer, servo_id, self.protective_torque)
+    }
+}
+
+/// Picks the value of register `R` out of a block read from [`MaxTorqueLimit`] on.
+fn field<R: Writable>(block: &[u8]) -> R::Value {
+    let offset = (R::ADDRESS - MaxTorqueLimit::ADDRESS) as usize;
+    let raw = match R::Value::WIDTH {
+        1 => block[offset] as u16,
+        _ => u16::from_le_bytes([block[offset], block[offset + 1]]),
+    };
+    R::Value::from_raw(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockPort, status_packet};
+
+    fn tuning_block() -> [u8; TUNING_BLOCK_LENGTH as usize] {
+        let mut block = [0u8; TUNING_BLOCK_LENGTH as usize];
+        block[0..2].copy_from_slice(&1000u16.to_le_bytes()); // max torque
+        block[5] = 32; // P
+        block[6] = 32; // D
+        block[7] = 0; // I
+        block[10] = 1; // CW dead zone
+        block[11] = 1; // CCW dead zone
+        block[12..14].copy_from_slice(&500u16.to_le_bytes()); // protection current
+        block[18] = 20; // protective torque
+        block[19] = 200; // protection time
+        block[20] = 80; // overload torque
+        block[22] = 200; // over current protection time
+        block
+    }
+
+    #[test]
+    fn test_read_tuning() {
+        let mut port = MockPort::new(&status_packet(1, &tuning_block()));
+        let mut buffer = [0u8; 64];
+
+        let tuning = ServoTuning::read(&mut port, &mut buffer, 1).unwrap();
+
+        assert_eq!(&port.tx[4..7], &[0x02, 0x10, TUNING_BLOCK_LENGTH]);
+        assert_eq!(
+            tuning,
+            ServoTuning {
+                p_coefficient: 32,
+                i_coefficient: 0,
+                d_coefficient: 32,
+                cw_dead_zone: 1,
+                ccw_dead_zone: 1,
+                max_torque: 1000,
+                protection_current: 500,
+                over_current_protection_time: 200,
+                overload_torque: 80,
+                protection_time: 200,
+                protective_torque: 20,
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_and_verify_tuning() {
+        let mut buffer = [0u8; 64];
+        let tuning = ServoTuning {
+            p_coefficient: 16,
+            ..ServoTuning::default()
+        };
+
+        // Unlock, eleven register writes and lock
+        let mut port = MockPort::new(&[]);
+        for _ in 0..13 {
+            port = port.reply(&status_packet(1, &[]));
+        }
+        tuning.write(&mut port, &mut buffer, 1).unwrap();
+        assert!(port.rx.is_empty());
+        assert_eq!(&port.tx[12..15], &[0x03, 0x15, 16]);
+
+        let mut port = MockPort::new(&status_packet(1, &tuning_block()));
+        assert!(matches!(
+            tuning.verify(&mut port, &mut buffer, 1),
+            Err(ServoError::VerificationFailed(1))
+        ));
+
+        let mut port = MockPort::new(&[]);
+        let too_hot = ServoTuning {
+            overload_torque: 101,
+            ..tuning
+        };
+        assert!(matches!(
+            too_hot.write(&mut port, &mut buffer, 1),
+            Err(ServoError::ValueOutOfRange(0x24, 101))
+        ));
+        assert!(
+            port.tx.is_empty(),
+            "Nothing is written when a value is out of range"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Physical units for the raw values in the control table.
+//!
+//! Positions are 4096 steps per revolution, with the mid-point [`CENTER_POSITION`] as angle zero.
+//! Temperatures are already reported in whole °C and need no conversion.
+
+use core::f32::consts::PI;
+
+/// Steps in one full revolution of the output shaft.
+pub const STEPS_PER_REVOLUTION: u16 = 4096;
+/// Raw position that corresponds to an [`Angle`] of zero.
+pub const CENTER_POSITION: u16 = 2048;
+/// Largest raw position.
+pub const MAX_POSITION: u16 = STEPS_PER_REVOLUTION - 1;
+
+const RADIANS_PER_STEP: f32 = 2.0 * PI / STEPS_PER_REVOLUTION as f32;
+const MILLIAMPS_PER_COUNT: f32 = 6.5;
+
+/// Shaft angle in radians, zero at [`CENTER_POSITION`] and positive towards higher positions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Angle(f32);
+
+impl Angle {
+    pub const fn from_radians(radians: f32) -> Self {
+        Self(radians)
+    }
+
+    pub fn from_degrees(degrees: f32) -> Self {
+        Self(degrees.to_radians())
+    }
+
+    pub fn from_steps(position: u16) -> Self {
+        Self((position as f32 - CENTER_POSITION as f32) * RADIANS_PER_STEP)
+    }
+
+    pub const fn radians(self) -> f32 {
+        self.0
+    }
+
+    pub fn degrees(self) -> f32 {
+        self.0.to_degrees()
+    }
+
+    /// Nearest raw position, saturating at 0 and [`MAX_POSITION`].
+    pub fn to_steps(self) -> u16 {
+        (round(self.0 / RADIANS_PER_STEP) + CENTER_POSITION as i32).clamp(0, MAX_POSITION as i32)
+            as u16
+    }
+}
+
+/// Rotational speed in radians per second, negative when turning in reverse.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct AngularVelocity(f32);
+
+impl AngularVelocity {
+    pub const fn from_radians_per_second(radians_per_second: f32) -> Self {
+        Self(radians_per_second)
+    }
+
+    pub fn from_rpm(rpm: f32) -> Self {
+        Self(rpm * 2.0 * PI / 60.0)
+    }
+
+    pub fn from_steps_per_second(steps_per_second: i16) -> Self {
+        Self(steps_per_second as f32 * RADIANS_PER_STEP)
+    }
+
+    pub const fn radians_per_second(self) -> f32 {
+        self.0
+    }
+
+    pub fn rpm(self) -> f32 {
+        self.0 * 60.0 / (2.0 * PI)
+    }
+
+    /// Nearest speed in steps/s, saturating at the range of `i16`.
+    pub fn to_steps_per_second(self) -> i16 {
+        round(self.0 / RADIANS_PER_STEP).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    }
+}
+
+/// Supply voltage in volts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Voltage(f32);
+
+impl Voltage {
+    pub const fn from_volts(volts: f32) -> Self {
+        Self(volts)
+    }
+
+    /// From the register value in units of 0.1 V.
+    pub fn from_raw(raw: u8) -> Self {
+        Self(raw as f32 / 10.0)
+    }
+
+    pub const fn volts(self) -> f32 {
+        self.0
+    }
+
+    /// Nearest register value in units of 0.1 V, saturating at the range of `u8`.
+    pub fn to_raw(self) -> u8 {
+        round(self.0 * 10.0).clamp(0, u8::MAX as i32) as u8
+    }
+}
+
+/// Motor current in milliamps.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Current(f32);
+
+impl Current {
+    pub const fn from_milliamps(milliamps: f32) -> Self {
+        Self(milliamps)
+    }
+
+    /// From the register value in units of 6.5 mA.
+    pub fn from_raw(raw: u16) -> Self {
+        Self(raw as f32 * MILLIAMPS_PER_COUNT)
+    }
+
+    pub const fn milliamps(self) -> f32 {
+        self.0
+    }
+
+    pub fn amps(self) -> f32 {
+        self.0 / 1000.0
+    }
+
+    /// Nearest register value in units of 6.5 mA, saturating at the range of `u16`.
+    pub fn to_raw(self) -> u16 {
+        round(self.0 / MILLIAMPS_PER_COUNT).clamp(0, u16::MAX as i32) as u16
+    }
+}
+
+/// Rounds half away from zero; `f32::round` needs std.
+pub(crate) fn round(value: f32) -> i32 {
+    if value < 0.0 {
+        (value - 0.5) as i32
+    } else {
+        (value + 0.5) as i32
+    }
+}
+
+/// Square root by Newton's method, 0 for negative values; `f32::sqrt` needs std.
+pub(crate) fn sqrt(value: f32) -> f32 {
+    if value <= 0.0 {
+        return 0.0;
+    }
+    // Halving the exponent bits is a close first guess
+    let mut root = f32::from_bits((value.to_bits() >> 1) + 0x1fc0_0000);
+    for _ in 0..6 {
+        root = (root + value / root) / 2.0;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_angle() {
+        assert_close(Angle::from_steps(CENTER_POSITION).radians(), 0.0);
+        assert_close(Angle::from_steps(3072).degrees(), 90.0);
+        assert_close(Angle::from_steps(0).radians(), -PI);
+
+        assert_eq!(Angle::from_degrees(-90.0).to_steps(), 1024);
+        assert_eq!(Angle::from_radians(PI / 2.0).to_steps(), 3072);
+        assert_eq!(Angle::from_degrees(0.05).to_steps(), CENTER_POSITION + 1);
+        assert_eq!(Angle::from_degrees(400.0).to_steps(), MAX_POSITION);
+        assert_eq!(Angle::from_degrees(-400.0).to_steps(), 0);
+    }
+
+    #[test]
+    fn test_angular_velocity() {
+        assert_close(AngularVelocity::from_steps_per_second(4096).rpm(), 60.0);
+        assert_close(
+            AngularVelocity::from_steps_per_second(-2048).radians_per_second(),
+            -PI,
+        );
+        assert_eq!(
+            AngularVelocity::from_rpm(-30.0).to_steps_per_second(),
+            -2048
+        );
+        assert_eq!(
+            AngularVelocity::from_rpm(1e6).to_steps_per_second(),
+            i16::MAX
+        );
+    }
+
+    #[test]
+    fn test_voltage_and_current() {
+        assert_close(Voltage::from_raw(74).volts(), 7.4);
+        assert_eq!(Voltage::from_volts(12.0).to_raw(), 120);
+        assert_close(Current::from_raw(100).milliamps(), 650.0);
+        assert_close(Current::from_raw(200).amps(), 1.3);
+        assert_eq!(Current::from_milliamps(13.0).to_raw(), 2);
+    }
+
+    #[test]
+    fn test_sqrt() {
+        for value in [1e-6, 0.25, 2.0, 1000.0, 4096.0 * 4096.0, 1e12] {
+            assert!(
+                (sqrt(value) * sqrt(value) / value - 1.0).abs() < 1e-5,
+                "{value}"
+            );
+        }
+        assert_eq!(sqrt(-1.0), 0.0);
+    }
+}